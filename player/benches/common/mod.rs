@@ -0,0 +1,55 @@
+//! Helpers shared by the benchmarks.
+
+use player::IdentityPassThroughFactory;
+
+pub type Global = wgc::hub::Global<IdentityPassThroughFactory>;
+
+const BACKENDS: &[wgt::Backend] = &[
+    wgt::Backend::Vulkan,
+    wgt::Backend::Metal,
+    wgt::Backend::Dx12,
+    wgt::Backend::Dx11,
+    wgt::Backend::Gl,
+];
+
+pub fn create_global() -> Global {
+    Global::new("bench", IdentityPassThroughFactory, wgt::Backends::all())
+}
+
+/// Create a device on the first backend that has an adapter, or return
+/// `None` if there is none.
+pub fn create_device(global: &Global) -> Option<wgc::id::DeviceId> {
+    for &backend in BACKENDS {
+        let adapter = match global.request_adapter(
+            &wgc::instance::RequestAdapterOptions {
+                power_preference: wgt::PowerPreference::HighPerformance,
+                compatible_surface: None,
+            },
+            wgc::instance::AdapterInputs::IdSet(&[wgc::id::TypedId::zip(0, 0, backend)], |id| {
+                id.backend()
+            }),
+        ) {
+            Ok(adapter) => adapter,
+            Err(_) => continue,
+        };
+
+        let device = wgc::id::TypedId::zip(0, 0, backend);
+        let (_, error) = wgc::gfx_select!(adapter => global.adapter_request_device(
+            adapter,
+            &wgt::DeviceDescriptor {
+                label: None,
+                features: wgt::Features::empty(),
+                limits: wgt::Limits::default(),
+            },
+            None,
+            device
+        ));
+        if let Some(e) = error {
+            panic!("{:?}", e);
+        }
+        println!("Backend {:?}", backend);
+        return Some(device);
+    }
+    println!("No adapter found");
+    None
+}
@@ -0,0 +1,105 @@
+//! Counts the command encoders created while submitting frames.
+//!
+//! Every submission stitches its barriers into a "_Transit" command buffer.
+//! Once the first frames are done, their encoders are reset and reused, so
+//! steady-state frames shouldn't create any.
+
+mod common;
+
+use std::time::Instant;
+
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 1000;
+const BUFFER_SIZE: wgt::BufferAddress = 1 << 16;
+
+fn main() {
+    env_logger::init();
+
+    let global = common::create_global();
+    let device = match common::create_device(&global) {
+        Some(device) => device,
+        None => return,
+    };
+    let backend = device.backend();
+    let mut ids = wgc::hub::IdentityManager::default();
+
+    let mut create_buffer = |usage| {
+        let (buffer, error) = wgc::gfx_select!(device => global.device_create_buffer(
+            device,
+            &wgt::BufferDescriptor {
+                label: None,
+                size: BUFFER_SIZE,
+                usage,
+                mapped_at_creation: false,
+            },
+            ids.alloc(backend)
+        ));
+        if let Some(e) = error {
+            panic!("{:?}", e);
+        }
+        buffer
+    };
+    let src = create_buffer(wgt::BufferUsages::COPY_SRC | wgt::BufferUsages::COPY_DST);
+    let dst = create_buffer(wgt::BufferUsages::COPY_SRC | wgt::BufferUsages::COPY_DST);
+
+    // Copy back and forth, so that each frame needs barriers.
+    let mut frame = |index: u32| {
+        let (src, dst) = if index % 2 == 0 {
+            (src, dst)
+        } else {
+            (dst, src)
+        };
+        let (encoder, error) = wgc::gfx_select!(device => global.device_create_command_encoder(
+            device,
+            &wgt::CommandEncoderDescriptor { label: None },
+            ids.alloc(backend)
+        ));
+        if let Some(e) = error {
+            panic!("{:?}", e);
+        }
+        wgc::gfx_select!(device => global.command_encoder_copy_buffer_to_buffer(
+            encoder, src, 0, dst, 0, BUFFER_SIZE
+        ))
+        .unwrap();
+        let (cmd_buf, error) = wgc::gfx_select!(device => global.command_encoder_finish(
+            encoder,
+            &wgt::CommandBufferDescriptor { label: None }
+        ));
+        if let Some(e) = error {
+            panic!("{:?}", e);
+        }
+        wgc::gfx_select!(device => global.queue_submit(device, &[cmd_buf])).unwrap();
+        wgc::gfx_select!(device => global.device_poll(device, false)).unwrap();
+        ids.free(cmd_buf);
+    };
+
+    for index in 0..WARMUP_FRAMES {
+        frame(index);
+    }
+    let warmup_encoders =
+        wgc::gfx_select!(device => global.device_created_command_encoders(device)).unwrap();
+
+    let start = Instant::now();
+    for index in 0..FRAMES {
+        frame(index);
+    }
+    let elapsed = start.elapsed();
+    let encoders = wgc::gfx_select!(device => global.device_created_command_encoders(device))
+        .unwrap()
+        - warmup_encoders;
+
+    println!(
+        "{} command encoders created over {} warm-up frames",
+        warmup_encoders, WARMUP_FRAMES
+    );
+    println!(
+        "{} command encoders created over {} frames ({:.3} per frame), {:?} per frame",
+        encoders,
+        FRAMES,
+        encoders as f64 / FRAMES as f64,
+        elapsed / FRAMES
+    );
+
+    wgc::gfx_select!(device => global.device_poll(device, true)).unwrap();
+    wgc::gfx_select!(device => global.clear_backend(()));
+}
@@ -0,0 +1,145 @@
+//! Compares the ways `queue_write_texture` can stage a large texture upload
+//! whose rows don't have the pitch required for copying them.
+//!
+//! For each strategy, the time spent in the write call itself is reported
+//! apart from the time until the GPU is done with the upload.
+
+mod common;
+
+use std::{
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
+use wgc::device::queue::WriteTextureRepack;
+
+const ITERATIONS: u32 = 20;
+// Rows of 8000 bytes, which no backend can copy from without padding.
+const WIDTH: u32 = 2000;
+const HEIGHT: u32 = 2000;
+const FORMAT: wgt::TextureFormat = wgt::TextureFormat::Rgba8Unorm;
+const BYTES_PER_TEXEL: u32 = 4;
+
+#[derive(Clone, Copy, Debug)]
+enum Strategy {
+    /// `WriteTextureRepack::Inline`.
+    Inline,
+    /// `WriteTextureRepack::GpuCopy`.
+    GpuCopy,
+    /// The rows are padded by the caller, and staged with
+    /// `queue_write_texture_prepadded`. The padding is included in the time
+    /// of the write, since it could be done on another thread.
+    Prepadded,
+}
+
+fn main() {
+    env_logger::init();
+
+    let global = common::create_global();
+    let device = match common::create_device(&global) {
+        Some(device) => device,
+        None => return,
+    };
+    let backend = device.backend();
+    let mut ids = wgc::hub::IdentityManager::default();
+
+    let size = wgt::Extent3d {
+        width: WIDTH,
+        height: HEIGHT,
+        depth_or_array_layers: 1,
+    };
+    let (texture, error) = wgc::gfx_select!(device => global.device_create_texture(
+        device,
+        &wgt::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgt::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgt::TextureUsages::COPY_DST,
+        },
+        ids.alloc(backend)
+    ));
+    if let Some(e) = error {
+        panic!("{:?}", e);
+    }
+    let destination = wgc::command::ImageCopyTexture {
+        texture,
+        mip_level: 0,
+        origin: wgt::Origin3d::ZERO,
+        aspect: wgt::TextureAspect::All,
+    };
+
+    let bytes_per_row = WIDTH * BYTES_PER_TEXEL;
+    let data = (0..bytes_per_row * HEIGHT)
+        .map(|i| i as u8)
+        .collect::<Vec<_>>();
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(bytes_per_row),
+        rows_per_image: None,
+    };
+    let alignment =
+        wgc::gfx_select!(device => global.texture_bytes_per_row_alignment(device, FORMAT)).unwrap();
+    let padded_bytes_per_row = (bytes_per_row + alignment - 1) / alignment * alignment;
+
+    for &strategy in &[Strategy::Inline, Strategy::GpuCopy, Strategy::Prepadded] {
+        let repack = match strategy {
+            Strategy::GpuCopy => WriteTextureRepack::GpuCopy,
+            Strategy::Inline | Strategy::Prepadded => WriteTextureRepack::Inline,
+        };
+        wgc::gfx_select!(device => global.device_set_write_texture_repack(device, repack)).unwrap();
+
+        let mut write_time = Duration::default();
+        let mut total_time = Duration::default();
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            match strategy {
+                Strategy::Inline | Strategy::GpuCopy => {
+                    wgc::gfx_select!(device => global.queue_write_texture(
+                        device,
+                        &destination,
+                        &data,
+                        &layout,
+                        &size
+                    ))
+                    .unwrap();
+                }
+                Strategy::Prepadded => {
+                    let mut padded = vec![0; (padded_bytes_per_row * HEIGHT) as usize];
+                    for (src, dst) in data
+                        .chunks(bytes_per_row as usize)
+                        .zip(padded.chunks_mut(padded_bytes_per_row as usize))
+                    {
+                        dst[..src.len()].copy_from_slice(src);
+                    }
+                    let padded_layout = wgt::ImageDataLayout {
+                        bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                        ..layout
+                    };
+                    wgc::gfx_select!(device => global.queue_write_texture_prepadded(
+                        device,
+                        &destination,
+                        &padded,
+                        &padded_layout,
+                        &size
+                    ))
+                    .unwrap();
+                }
+            }
+            write_time += start.elapsed();
+            wgc::gfx_select!(device => global.queue_submit(device, &[])).unwrap();
+            wgc::gfx_select!(device => global.device_poll(device, true)).unwrap();
+            total_time += start.elapsed();
+        }
+
+        println!(
+            "{:?}: {:?} in the write, {:?} until the GPU is done",
+            strategy,
+            write_time / ITERATIONS,
+            total_time / ITERATIONS
+        );
+    }
+
+    wgc::gfx_select!(device => global.clear_backend(()));
+}
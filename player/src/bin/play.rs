@@ -49,7 +49,18 @@ fn main() {
         global.instance_create_surface(&window, wgc::id::TypedId::zip(0, 1, wgt::Backend::Empty));
 
     let device = match actions.pop() {
-        Some(trace::Action::Init { desc, backend }) => {
+        Some(trace::Action::Init {
+            desc,
+            backend,
+            version,
+        }) => {
+            if version > trace::FILE_VERSION {
+                panic!(
+                    "Trace version {} is newer than the supported version {}",
+                    version,
+                    trace::FILE_VERSION
+                );
+            }
             log::info!("Initializing the device for backend: {:?}", backend);
             let adapter = global
                 .request_adapter(
@@ -74,12 +74,41 @@ impl GlobalPlay for wgc::hub::Global<IdentityPassThroughFactory> {
                 trace::Command::CopyTextureToBuffer { src, dst, size } => self
                     .command_encoder_copy_texture_to_buffer::<A>(encoder, &src, &dst, &size)
                     .unwrap(),
+                trace::Command::CopyTextureToBufferTight { src, dst, size } => {
+                    let layout = self
+                        .command_encoder_copy_texture_to_buffer_tight::<A>(
+                            encoder,
+                            &src,
+                            dst.buffer,
+                            dst.layout.offset,
+                            &size,
+                        )
+                        .unwrap();
+                    if layout.bytes_per_row != dst.layout.bytes_per_row
+                        || layout.rows_per_image != dst.layout.rows_per_image
+                    {
+                        log::warn!(
+                            "Copy into {:?} was recorded with layout {:?}, replayed with {:?}",
+                            dst.buffer,
+                            dst.layout,
+                            layout
+                        );
+                    }
+                }
                 trace::Command::CopyTextureToTexture { src, dst, size } => self
                     .command_encoder_copy_texture_to_texture::<A>(encoder, &src, &dst, &size)
                     .unwrap(),
                 trace::Command::ClearBuffer { dst, offset, size } => self
                     .command_encoder_clear_buffer::<A>(encoder, dst, offset, size)
                     .unwrap(),
+                trace::Command::FillBuffer {
+                    dst,
+                    offset,
+                    size,
+                    value,
+                } => self
+                    .command_encoder_fill_buffer::<A>(encoder, dst, offset, size, value)
+                    .unwrap(),
                 trace::Command::ClearImage {
                     dst,
                     subresource_range,
@@ -348,11 +377,28 @@ impl GlobalPlay for wgc::hub::Global<IdentityPassThroughFactory> {
                 data,
                 layout,
                 size,
+                staging,
             } => {
                 let bin = std::fs::read(dir.join(data)).unwrap();
-                self.queue_write_texture::<A>(device, &to, &bin, &layout, &size)
+                match staging {
+                    Some(staging) => self
+                        .queue_write_texture_staged::<A>(
+                            device, &to, &bin, &layout, &size, &staging,
+                        )
+                        .unwrap(),
+                    // Older traces don't record it, so the device picks its own.
+                    None => self
+                        .queue_write_texture::<A>(device, &to, &bin, &layout, &size)
+                        .unwrap(),
+                }
+            }
+            Action::SetZeroWriteFillThreshold(threshold) => {
+                self.device_set_zero_write_fill_threshold::<A>(device, threshold)
                     .unwrap();
             }
+            Action::LabelSubmission(..) => {
+                // only informative, already logged above
+            }
             Action::Submit(_index, ref commands) if commands.is_empty() => {
                 self.queue_submit::<A>(device, &[]).unwrap();
             }
@@ -221,5 +221,8 @@ impl Corpus {
 fn test_api() {
     env_logger::init();
 
-    Corpus::run_from(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/all.ron"))
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    Corpus::run_from(dir.join("all.ron"));
+    // Patterns other than zero can't be written on every backend.
+    Corpus::run_from(dir.join("fill-patterns.ron"));
 }
@@ -23,6 +23,8 @@ pub enum ClearError {
     MissingClearCommandsFeature,
     #[error("command encoder {0:?} is invalid")]
     InvalidCommandEncoder(CommandEncoderId),
+    #[error("buffers can't be filled with the pattern {value:#010x} on {backend:?}")]
+    UnsupportedFillValue { value: u32, backend: wgt::Backend },
     #[error("buffer {0:?} is invalid or destroyed")]
     InvalidBuffer(BufferId),
     #[error("texture {0:?} is invalid or destroyed")]
@@ -60,6 +62,18 @@ whereas subesource range specified start {subresource_base_array_layer} and coun
     },
 }
 
+/// Checks if buffers can be filled with the 4-byte `value` pattern on `backend`.
+///
+/// DX12 fills buffers by copying from a zeroed one, and Metal with a single byte.
+fn is_fill_value_supported(backend: wgt::Backend, value: u32) -> bool {
+    let bytes = value.to_le_bytes();
+    match backend {
+        wgt::Backend::Dx12 => value == 0,
+        wgt::Backend::Metal => bytes.iter().all(|&byte| byte == bytes[0]),
+        _ => true,
+    }
+}
+
 impl<G: GlobalIdentityHandlerFactory> Global<G> {
     pub fn command_encoder_clear_buffer<A: HalApi>(
         &self,
@@ -67,6 +81,33 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         dst: BufferId,
         offset: BufferAddress,
         size: Option<BufferSize>,
+    ) -> Result<(), ClearError> {
+        self.fill_buffer_impl::<A>(command_encoder_id, dst, offset, size, 0, true)
+    }
+
+    /// Fill the buffer range with the 4-byte `value` pattern, repeated.
+    ///
+    /// Unlike clears, this doesn't require `Features::CLEAR_COMMANDS`, but the
+    /// backend has to support the pattern, see `is_fill_value_supported`.
+    pub fn command_encoder_fill_buffer<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        dst: BufferId,
+        offset: BufferAddress,
+        size: Option<BufferSize>,
+        value: u32,
+    ) -> Result<(), ClearError> {
+        self.fill_buffer_impl::<A>(command_encoder_id, dst, offset, size, value, false)
+    }
+
+    fn fill_buffer_impl<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        dst: BufferId,
+        offset: BufferAddress,
+        size: Option<BufferSize>,
+        value: u32,
+        is_clear: bool,
     ) -> Result<(), ClearError> {
         profiling::scope!("CommandEncoder::fill_buffer");
 
@@ -79,12 +120,27 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
 
         #[cfg(feature = "trace")]
         if let Some(ref mut list) = cmd_buf.commands {
-            list.push(TraceCommand::ClearBuffer { dst, offset, size });
+            list.push(if is_clear {
+                TraceCommand::ClearBuffer { dst, offset, size }
+            } else {
+                TraceCommand::FillBuffer {
+                    dst,
+                    offset,
+                    size,
+                    value,
+                }
+            });
         }
 
-        if !cmd_buf.support_fill_buffer_texture {
+        if is_clear && !cmd_buf.support_fill_buffer_texture {
             return Err(ClearError::MissingClearCommandsFeature);
         }
+        if !is_fill_value_supported(A::VARIANT, value) {
+            return Err(ClearError::UnsupportedFillValue {
+                value,
+                backend: A::VARIANT,
+            });
+        }
 
         let (dst_buffer, dst_pending) = cmd_buf
             .trackers
@@ -143,7 +199,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         let cmd_buf_raw = cmd_buf.encoder.open();
         unsafe {
             cmd_buf_raw.transition_buffers(dst_barrier);
-            cmd_buf_raw.fill_buffer(dst_raw, offset..end, 0);
+            cmd_buf_raw.fill_buffer(dst_raw, offset..end, value);
         }
         Ok(())
     }
@@ -266,3 +322,22 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         Ok(())
     }
 }
+
+#[test]
+fn test_is_fill_value_supported() {
+    for &backend in [
+        wgt::Backend::Vulkan,
+        wgt::Backend::Metal,
+        wgt::Backend::Dx12,
+        wgt::Backend::Gl,
+    ]
+    .iter()
+    {
+        assert!(is_fill_value_supported(backend, 0));
+    }
+    assert!(is_fill_value_supported(wgt::Backend::Vulkan, 0xDEADBEEF));
+    assert!(is_fill_value_supported(wgt::Backend::Gl, 0xDEADBEEF));
+    assert!(is_fill_value_supported(wgt::Backend::Metal, 0xABABABAB));
+    assert!(!is_fill_value_supported(wgt::Backend::Metal, 0xDEADBEEF));
+    assert!(!is_fill_value_supported(wgt::Backend::Dx12, 0xABABABAB));
+}
@@ -74,10 +74,14 @@ pub struct CommandBuffer<A: hal::Api> {
     status: CommandEncoderStatus,
     pub(crate) device_id: Stored<id::DeviceId>,
     pub(crate) trackers: TrackerSet,
-    pub(crate) used_swap_chains: SmallVec<[Stored<id::SwapChainId>; 1]>,
+    /// The swap chains rendered to, along with the frame of their texture,
+    /// as counted by `SwapChain::acquired_frames`.
+    pub(crate) used_swap_chains: SmallVec<[(Stored<id::SwapChainId>, u64); 1]>,
     pub(crate) buffer_memory_init_actions: Vec<MemoryInitTrackerAction<id::BufferId>>,
     limits: wgt::Limits,
     support_fill_buffer_texture: bool,
+    /// Whether the copies are recorded in debug groups, for GPU captures.
+    copy_debug_markers: bool,
     #[cfg(feature = "trace")]
     pub(crate) commands: Option<Vec<crate::device::trace::Command>>,
 }
@@ -89,6 +93,7 @@ impl<A: HalApi> CommandBuffer<A> {
         limits: wgt::Limits,
         _downlevel: wgt::DownlevelCapabilities,
         features: wgt::Features,
+        copy_debug_markers: bool,
         #[cfg(feature = "trace")] enable_tracing: bool,
         label: &Label,
     ) -> Self {
@@ -106,6 +111,7 @@ impl<A: HalApi> CommandBuffer<A> {
             buffer_memory_init_actions: Default::default(),
             limits,
             support_fill_buffer_texture: features.contains(wgt::Features::CLEAR_COMMANDS),
+            copy_debug_markers,
             #[cfg(feature = "trace")]
             commands: if enable_tracing {
                 Some(Vec::new())
@@ -164,6 +170,28 @@ impl<A: hal::Api> CommandBuffer<A> {
         }
     }
 
+    /// Start encoding the transitions to put in front of the finished command
+    /// buffer, which `into_baked` then hands over with the encoder open.
+    ///
+    /// This does nothing if they are already being encoded, from a submission
+    /// that failed before they were done.
+    pub(crate) fn begin_transit(&mut self, label: &str) -> Result<(), hal::DeviceError> {
+        debug_assert!(self.is_finished());
+        if !self.encoder.is_open {
+            unsafe { self.encoder.raw.begin_encoding(Some(label))? };
+            self.encoder.is_open = true;
+        }
+        Ok(())
+    }
+
+    /// Drop whatever is being encoded, before the command buffer is destroyed.
+    pub(crate) fn discard_encoding(&mut self) {
+        if self.encoder.is_open {
+            self.encoder.is_open = false;
+            unsafe { self.encoder.raw.discard_encoding() };
+        }
+    }
+
     pub(crate) fn into_baked(self) -> BackedCommands<A> {
         BackedCommands {
             encoder: self.encoder.raw,
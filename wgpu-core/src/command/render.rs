@@ -502,7 +502,8 @@ struct RenderPassInfo<'a, A: hal::Api> {
     context: RenderPassContext,
     trackers: StatefulTrackerSubset,
     render_attachments: AttachmentDataVec<RenderAttachment<'a>>,
-    used_swap_chain: Option<Stored<id::SwapChainId>>,
+    /// The swap chain rendered to, along with the frame of its texture.
+    used_swap_chain: Option<(Stored<id::SwapChainId>, u64)>,
     is_ds_read_only: bool,
     extent: wgt::Extent3d,
     _phantom: PhantomData<A>,
@@ -744,7 +745,10 @@ impl<'a, A: HalApi> RenderPassInfo<'a, A> {
             context,
             trackers: StatefulTrackerSubset::new(A::VARIANT),
             render_attachments,
-            used_swap_chain: used_swap_chain.map(|(sc_id, _)| sc_id),
+            used_swap_chain: used_swap_chain.map(|(sc_id, _)| {
+                let frame = swap_chain_guard[sc_id.value].acquired_frames;
+                (sc_id, frame)
+            }),
             is_ds_read_only,
             extent,
             _phantom: PhantomData,
@@ -756,14 +760,19 @@ impl<'a, A: HalApi> RenderPassInfo<'a, A> {
         raw: &mut A::CommandEncoder,
         texture_guard: &Storage<Texture<A>, id::TextureId>,
         swap_chain_guard: &Storage<SwapChain<A>, id::SwapChainId>,
-    ) -> Result<(StatefulTrackerSubset, Option<Stored<id::SwapChainId>>), RenderPassErrorInner>
-    {
+    ) -> Result<
+        (
+            StatefulTrackerSubset,
+            Option<(Stored<id::SwapChainId>, u64)>,
+        ),
+        RenderPassErrorInner,
+    > {
         profiling::scope!("finish", "RenderPassInfo");
         unsafe {
             raw.end_render_pass();
         }
 
-        if let Some(ref sc_id) = self.used_swap_chain {
+        if let Some((ref sc_id, _)) = self.used_swap_chain {
             let &(_, ref suf_texture) = swap_chain_guard[sc_id.value]
                 .acquired_texture
                 .as_ref()
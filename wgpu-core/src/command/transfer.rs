@@ -6,15 +6,15 @@ use crate::{
     hub::{Global, GlobalIdentityHandlerFactory, HalApi, Storage, Token},
     id::{BufferId, CommandEncoderId, TextureId},
     memory_init_tracker::{MemoryInitKind, MemoryInitTrackerAction},
-    resource::{Texture, TextureErrorDimension},
+    resource::{Buffer, BufferMapState, Texture, TextureErrorDimension, TextureTiling},
     track::TextureSelector,
 };
 
-use hal::CommandEncoder as _;
+use hal::{CommandEncoder as _, Device as _};
 use thiserror::Error;
 use wgt::{BufferAddress, BufferUsages, Extent3d, TextureUsages};
 
-use std::iter;
+use std::{iter, num::NonZeroU32, ops::Range};
 
 pub type ImageCopyBuffer = wgt::ImageCopyBuffer<BufferId>;
 pub type ImageCopyTexture = wgt::ImageCopyTexture<TextureId>;
@@ -34,8 +34,22 @@ pub enum TransferError {
     InvalidTexture(TextureId),
     #[error("Source and destination cannot be the same buffer")]
     SameSourceDestinationBuffer,
+    #[error("texture {dst:?} doesn't have the same format, size, mip levels, sample count and dimension as {src:?}, so it can't be re-tiled into")]
+    RetileMismatch { src: TextureId, dst: TextureId },
+    #[error("texture {0:?} has a linear tiling, so it can't be re-tiled into")]
+    LinearRetileDestination(TextureId),
+    #[error("source and destination ranges of the copy overlap in memory")]
+    OverlappingCopyRange,
+    #[error("texture {texture:?} can't be used as {current:?} and {requested:?} by the same copy")]
+    TextureUsageConflict {
+        texture: TextureId,
+        current: hal::TextureUses,
+        requested: hal::TextureUses,
+    },
     #[error("source buffer/texture is missing the `COPY_SRC` usage flag")]
     MissingCopySrcUsageFlag,
+    #[error("source buffer {0:?} is mapped, or about to be mapped")]
+    MappedSourceBuffer(BufferId),
     #[error("destination buffer/texture is missing the `COPY_DST` usage flag")]
     MissingCopyDstUsageFlag(Option<BufferId>, Option<TextureId>),
     #[error("copy of {start_offset}..{end_offset} would end up overrunning the bounds of the {side:?} buffer of size {buffer_size}")]
@@ -45,6 +59,21 @@ pub enum TransferError {
         buffer_size: BufferAddress,
         side: CopySide,
     },
+    #[error("copy of {size} bytes at offset {start_offset} of the {side:?} buffer overflows the buffer address space")]
+    BufferAddressOverflow {
+        start_offset: BufferAddress,
+        size: BufferAddress,
+        side: CopySide,
+    },
+    #[error("copy of {start_offset}..{end_offset} would end up overrunning the bounds of the {side:?} buffer of size {buffer_size}: the last row needs {bytes_in_last_row} bytes, even though the rows before it are padded to {bytes_per_row} bytes")]
+    BufferOverrunInLastRow {
+        start_offset: BufferAddress,
+        end_offset: BufferAddress,
+        buffer_size: BufferAddress,
+        bytes_per_row: BufferAddress,
+        bytes_in_last_row: BufferAddress,
+        side: CopySide,
+    },
     #[error("copy of {dimension:?} {start_offset}..{end_offset} would end up overrunning the bounds of the {side:?} texture of {dimension:?} size {texture_size}")]
     TextureOverrun {
         start_offset: u32,
@@ -60,6 +89,8 @@ pub enum TransferError {
     },
     #[error("unable to select texture mip level {level} out of {total}")]
     InvalidTextureMipLevel { level: u32, total: u32 },
+    #[error("unable to select texture array layers {start}..{end} out of {total}")]
+    InvalidTextureArrayLayers { start: u32, end: u32, total: u32 },
     #[error("buffer offset {0} is not aligned to block size or `COPY_BUFFER_ALIGNMENT`")]
     UnalignedBufferOffset(BufferAddress),
     #[error("copy size {0} does not respect `COPY_BUFFER_ALIGNMENT`")]
@@ -80,18 +111,85 @@ pub enum TransferError {
     UnspecifiedRowsPerImage,
     #[error("number of bytes per row is less than the number of bytes in a complete row")]
     InvalidBytesPerRow,
+    #[error("the {side:?} buffer layout has {bytes_per_row} bytes per row, fewer than the {bytes_in_row} bytes in a row of the {width} texels wide texture region")]
+    LayoutNarrowerThanCopy {
+        bytes_per_row: BufferAddress,
+        bytes_in_row: BufferAddress,
+        width: u32,
+        side: CopySide,
+    },
     #[error("image is 1D and the copy height and depth are not both set to 1")]
     InvalidCopySize,
-    #[error("number of rows per image is invalid")]
+    #[error("number of rows per image is less than the copy height")]
     InvalidRowsPerImage,
+    #[error("number of rows per image ({rows_per_image}) is less than the copy height ({copy_height}), so the {layers} copied images would overlap")]
+    InvalidRowsPerImageForLayers {
+        rows_per_image: u32,
+        copy_height: u32,
+        layers: u32,
+    },
     #[error("source and destination layers have different aspects")]
     MismatchedAspects,
+    #[error("copy of {size} bytes exceeds the maximum of {max} bytes for a single copy")]
+    BufferCopyTooLarge {
+        size: BufferAddress,
+        max: BufferAddress,
+    },
+    #[error("source format {src:?} and destination format {dst:?} can't be copied between without a conversion")]
+    MismatchedTextureFormats {
+        src: wgt::TextureFormat,
+        dst: wgt::TextureFormat,
+    },
     #[error("copying from textures with format {0:?} is forbidden")]
     CopyFromForbiddenTextureFormat(wgt::TextureFormat),
     #[error("copying to textures with format {0:?} is forbidden")]
     CopyToForbiddenTextureFormat(wgt::TextureFormat),
 }
 
+/// Label of the debug group recorded around a copy, if `enabled`.
+///
+/// The label is only formatted when it's going to be used.
+pub(crate) fn copy_debug_marker(enabled: bool, label: impl FnOnce() -> String) -> Option<String> {
+    if enabled {
+        Some(label())
+    } else {
+        None
+    }
+}
+
+/// Statistics about a copy recorded into a command encoder.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CopyStats {
+    /// Number of bytes transferred by the copy. For copies between buffers and
+    /// textures, this includes the row padding in the buffer.
+    pub bytes: BufferAddress,
+    /// Rough estimate of the GPU time the copy takes, in nanoseconds. See
+    /// [`estimate_transfer_ns`].
+    pub estimated_ns: u64,
+}
+
+impl CopyStats {
+    fn new(bytes: BufferAddress) -> Self {
+        Self {
+            bytes,
+            estimated_ns: estimate_transfer_ns(bytes),
+        }
+    }
+}
+
+/// Returns a rough estimate of the time, in nanoseconds, the GPU takes to
+/// transfer `bytes` bytes.
+///
+/// This is not a measured value: it divides the byte count by the bandwidth
+/// of a PCIe 3.0 x16 link, 16 lanes of 8 GT/s with 128b/130b encoding, which
+/// is about 16 GB/s. It is the same on every backend, and is only meant to
+/// let schedulers order or defer large transfers against each other.
+pub fn estimate_transfer_ns(bytes: BufferAddress) -> u64 {
+    // Bytes per nanosecond, i.e. GB/s.
+    const BANDWIDTH: BufferAddress = 16;
+    bytes / BANDWIDTH + (bytes % BANDWIDTH != 0) as u64
+}
+
 /// Error encountered while attempting to do a copy on a command encoder.
 #[derive(Clone, Debug, Error)]
 pub enum CopyError {
@@ -101,9 +199,141 @@ pub enum CopyError {
     Transfer(#[from] TransferError),
 }
 
+/// Returns the aspects of a texture of the given format that a copy with
+/// `aspect` operates on. `TextureAspect::All` selects every aspect the format has.
+///
+/// An aspect the format doesn't have is an error, it never falls back to the
+/// color aspect. There are no multi-planar formats, so no aspect selects a
+/// plane: the only formats with several aspects are depth/stencil ones.
+pub(crate) fn texture_copy_aspect(
+    format: wgt::TextureFormat,
+    aspect: wgt::TextureAspect,
+) -> Result<hal::FormatAspects, TransferError> {
+    let copy_aspect = hal::FormatAspects::from(format) & hal::FormatAspects::from(aspect);
+    if copy_aspect.is_empty() {
+        return Err(TransferError::InvalidTextureAspect { format, aspect });
+    }
+    Ok(copy_aspect)
+}
+
+/// Returns the aspects copied between two textures.
+///
+/// A single aspect of a depth/stencil texture can be copied on its own,
+/// leaving the other untouched, as long as both sides select the same one.
+pub(crate) fn texture_to_texture_copy_aspect(
+    src_format: wgt::TextureFormat,
+    src_aspect: wgt::TextureAspect,
+    dst_format: wgt::TextureFormat,
+    dst_aspect: wgt::TextureAspect,
+) -> Result<hal::FormatAspects, TransferError> {
+    let src_copy_aspect = texture_copy_aspect(src_format, src_aspect)?;
+    let dst_copy_aspect = texture_copy_aspect(dst_format, dst_aspect)?;
+    if src_copy_aspect != dst_copy_aspect {
+        return Err(TransferError::MismatchedAspects);
+    }
+    Ok(src_copy_aspect)
+}
+
+/// Returns the format without its sRGB encoding, which stores the same bytes.
+fn linear_texture_format(format: wgt::TextureFormat) -> wgt::TextureFormat {
+    use wgt::TextureFormat as Tf;
+    match format {
+        Tf::Rgba8UnormSrgb => Tf::Rgba8Unorm,
+        Tf::Bgra8UnormSrgb => Tf::Bgra8Unorm,
+        Tf::Bc1RgbaUnormSrgb => Tf::Bc1RgbaUnorm,
+        Tf::Bc2RgbaUnormSrgb => Tf::Bc2RgbaUnorm,
+        Tf::Bc3RgbaUnormSrgb => Tf::Bc3RgbaUnorm,
+        Tf::Bc7RgbaUnormSrgb => Tf::Bc7RgbaUnorm,
+        Tf::Etc2RgbUnormSrgb => Tf::Etc2RgbUnorm,
+        Tf::Etc2RgbA1UnormSrgb => Tf::Etc2RgbA1Unorm,
+        Tf::Astc4x4RgbaUnormSrgb => Tf::Astc4x4RgbaUnorm,
+        Tf::Astc5x4RgbaUnormSrgb => Tf::Astc5x4RgbaUnorm,
+        Tf::Astc5x5RgbaUnormSrgb => Tf::Astc5x5RgbaUnorm,
+        Tf::Astc6x5RgbaUnormSrgb => Tf::Astc6x5RgbaUnorm,
+        Tf::Astc6x6RgbaUnormSrgb => Tf::Astc6x6RgbaUnorm,
+        Tf::Astc8x5RgbaUnormSrgb => Tf::Astc8x5RgbaUnorm,
+        Tf::Astc8x6RgbaUnormSrgb => Tf::Astc8x6RgbaUnorm,
+        Tf::Astc10x5RgbaUnormSrgb => Tf::Astc10x5RgbaUnorm,
+        Tf::Astc10x6RgbaUnormSrgb => Tf::Astc10x6RgbaUnorm,
+        Tf::Astc8x8RgbaUnormSrgb => Tf::Astc8x8RgbaUnorm,
+        Tf::Astc10x8RgbaUnormSrgb => Tf::Astc10x8RgbaUnorm,
+        Tf::Astc10x10RgbaUnormSrgb => Tf::Astc10x10RgbaUnorm,
+        Tf::Astc12x10RgbaUnormSrgb => Tf::Astc12x10RgbaUnorm,
+        Tf::Astc12x12RgbaUnormSrgb => Tf::Astc12x12RgbaUnorm,
+        other => other,
+    }
+}
+
+/// Returns whether texels can be copied between textures of formats `a` and `b`.
+///
+/// Copies never convert between formats, so the compatible formats are:
+/// - identical formats,
+/// - a format with sRGB encoding and its linear counterpart, in either
+///   direction, since the encoding only matters when sampling.
+///
+/// Same-block-size aliases, such as `Rgba8Unorm` and `Rgba8Snorm` or
+/// `R32Float` and `R32Uint`, are deliberately *not* compatible, unlike in
+/// Vulkan or D3D12 typeless groups: Metal blits only accept formats that
+/// match up to their sRGB encoding, so allowing them would make the result
+/// of this query backend-dependent. Formats that only share a block size,
+/// like `Rgba8Unorm` and `Bgra8Unorm`, would swizzle the texels and aren't
+/// compatible either.
+pub fn formats_copy_compatible(a: wgt::TextureFormat, b: wgt::TextureFormat) -> bool {
+    linear_texture_format(a) == linear_texture_format(b)
+}
+
+/// Checks that texels can be copied between the two formats byte for byte,
+/// see [`formats_copy_compatible`].
+pub(crate) fn validate_texture_copy_formats(
+    src: wgt::TextureFormat,
+    dst: wgt::TextureFormat,
+) -> Result<(), TransferError> {
+    if !formats_copy_compatible(src, dst) {
+        return Err(TransferError::MismatchedTextureFormats { src, dst });
+    }
+    Ok(())
+}
+
+/// Returns the array layers touched by a copy, and the Z origin of the copy
+/// within each layer.
+fn texture_copy_layers(
+    desc: &wgt::TextureDescriptor<()>,
+    copy_texture: &ImageCopyTexture,
+    copy_size: &Extent3d,
+    side: CopySide,
+) -> Result<(Range<u32>, u32), TransferError> {
+    match desc.dimension {
+        wgt::TextureDimension::D1 | wgt::TextureDimension::D2 => {
+            let total = desc.array_layer_count();
+            let end = copy_texture
+                .origin
+                .z
+                .checked_add(copy_size.depth_or_array_layers)
+                .ok_or(TransferError::TextureOverrun {
+                    start_offset: copy_texture.origin.z,
+                    end_offset: u32::MAX,
+                    texture_size: total,
+                    dimension: TextureErrorDimension::Z,
+                    side,
+                })?;
+            let layers = copy_texture.origin.z..end;
+            if layers.end > total {
+                return Err(TransferError::InvalidTextureArrayLayers {
+                    start: layers.start,
+                    end: layers.end,
+                    total,
+                });
+            }
+            Ok((layers, 0))
+        }
+        wgt::TextureDimension::D3 => Ok((0..1, copy_texture.origin.z)),
+    }
+}
+
 pub(crate) fn extract_texture_selector<A: hal::Api>(
     copy_texture: &ImageCopyTexture,
     copy_size: &Extent3d,
+    side: CopySide,
     texture_guard: &Storage<Texture<A>, TextureId>,
 ) -> Result<(TextureSelector, hal::TextureCopyBase, wgt::TextureFormat), TransferError> {
     let texture = texture_guard
@@ -111,22 +341,9 @@ pub(crate) fn extract_texture_selector<A: hal::Api>(
         .map_err(|_| TransferError::InvalidTexture(copy_texture.texture))?;
 
     let format = texture.desc.format;
-    let copy_aspect =
-        hal::FormatAspects::from(format) & hal::FormatAspects::from(copy_texture.aspect);
-    if copy_aspect.is_empty() {
-        return Err(TransferError::InvalidTextureAspect {
-            format,
-            aspect: copy_texture.aspect,
-        });
-    }
+    let copy_aspect = texture_copy_aspect(format, copy_texture.aspect)?;
 
-    let (layers, origin_z) = match texture.desc.dimension {
-        wgt::TextureDimension::D1 | wgt::TextureDimension::D2 => (
-            copy_texture.origin.z..copy_texture.origin.z + copy_size.depth_or_array_layers,
-            0,
-        ),
-        wgt::TextureDimension::D3 => (0..1, copy_texture.origin.z),
-    };
+    let (layers, origin_z) = texture_copy_layers(&texture.desc, copy_texture, copy_size, side)?;
     let base = hal::TextureCopyBase {
         origin: wgt::Origin3d {
             x: copy_texture.origin.x,
@@ -146,6 +363,142 @@ pub(crate) fn extract_texture_selector<A: hal::Api>(
     Ok((selector, base, format))
 }
 
+/// Checks that `size` bytes at `offset` fit in a buffer of `buffer_size` bytes,
+/// and returns the end offset of the range.
+pub(crate) fn validate_buffer_range(
+    offset: BufferAddress,
+    size: BufferAddress,
+    buffer_size: BufferAddress,
+    side: CopySide,
+) -> Result<BufferAddress, TransferError> {
+    let end_offset = offset
+        .checked_add(size)
+        .ok_or(TransferError::BufferAddressOverflow {
+            start_offset: offset,
+            size,
+            side: side.clone(),
+        })?;
+    if end_offset > buffer_size {
+        return Err(TransferError::BufferOverrun {
+            start_offset: offset,
+            end_offset,
+            buffer_size,
+            side,
+        });
+    }
+    Ok(end_offset)
+}
+
+/// Checks that a buffer copied from isn't mapped by the user.
+///
+/// A copy from a mapped buffer would read memory that the host is
+/// concurrently writing, and its submission is refused later on anyway.
+pub(crate) fn validate_source_buffer_unmapped<A: hal::Api>(
+    map_state: &BufferMapState<A>,
+    buffer_id: BufferId,
+) -> Result<(), TransferError> {
+    match *map_state {
+        BufferMapState::Idle => Ok(()),
+        _ => Err(TransferError::MappedSourceBuffer(buffer_id)),
+    }
+}
+
+/// Checks if the given ranges of two distinct buffers share memory.
+///
+/// Every buffer currently owns its own allocation, so this never happens.
+/// This is where sub-allocated buffers would have to compare their backing
+/// allocation and offsets, once they are introduced.
+fn buffer_ranges_alias<A: hal::Api>(
+    _src: &Buffer<A>,
+    _src_range: Range<BufferAddress>,
+    _dst: &Buffer<A>,
+    _dst_range: Range<BufferAddress>,
+) -> bool {
+    false
+}
+
+/// Checks if two non-empty ranges share an element.
+fn ranges_overlap<T: PartialOrd>(a: &Range<T>, b: &Range<T>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Checks that a copy from a texture into itself doesn't use any subresource
+/// as both its source and its destination.
+///
+/// The tracker keeps a single usage per subresource, so the destination usage
+/// would silently replace the source one.
+fn validate_copy_subresources(
+    source: TextureId,
+    src_range: &TextureSelector,
+    destination: TextureId,
+    dst_range: &TextureSelector,
+) -> Result<(), TransferError> {
+    if source == destination
+        && ranges_overlap(&src_range.levels, &dst_range.levels)
+        && ranges_overlap(&src_range.layers, &dst_range.layers)
+    {
+        return Err(TransferError::TextureUsageConflict {
+            texture: source,
+            current: hal::TextureUses::COPY_SRC,
+            requested: hal::TextureUses::COPY_DST,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the size of a texel block of `format` in linear data, when only
+/// `aspect` of the texture is copied.
+///
+/// Stencil is laid out as a single byte per texel in buffers, no matter how
+/// it's packed together with depth inside the texture.
+pub(crate) fn aspect_block_size(format: wgt::TextureFormat, aspect: hal::FormatAspects) -> u8 {
+    if aspect == hal::FormatAspects::STENCIL {
+        1
+    } else {
+        format.describe().block_size
+    }
+}
+
+/// Returns the number of bytes taken by `layers` of mip `mip_level` of a
+/// texture, when only `aspect` of it is stored and the rows are tightly packed.
+pub(crate) fn texture_subresource_size(
+    desc: &wgt::TextureDescriptor<()>,
+    mip_level: u32,
+    layers: Range<u32>,
+    aspect: hal::FormatAspects,
+) -> Result<BufferAddress, TransferError> {
+    let extent = desc
+        .mip_level_size(mip_level)
+        .ok_or(TransferError::InvalidTextureMipLevel {
+            level: mip_level,
+            total: desc.mip_level_count,
+        })?;
+    let total = desc.array_layer_count();
+    if layers.start > layers.end || layers.end > total {
+        return Err(TransferError::InvalidTextureArrayLayers {
+            start: layers.start,
+            end: layers.end,
+            total,
+        });
+    }
+    // 3D textures have a single layer made of all the depth slices of the mip
+    let slices = match desc.dimension {
+        wgt::TextureDimension::D1 | wgt::TextureDimension::D2 => 1,
+        wgt::TextureDimension::D3 => extent.depth_or_array_layers,
+    };
+
+    let (block_width, block_height) = desc.format.describe().block_dimensions;
+    let physical = extent.physical_size(desc.format);
+    let width_in_blocks = (physical.width / block_width as u32) as BufferAddress;
+    let height_in_blocks = (physical.height / block_height as u32) as BufferAddress;
+    let block_size = aspect_block_size(desc.format, aspect) as BufferAddress;
+    Ok(width_in_blocks
+        * height_in_blocks
+        * block_size
+        * slices as BufferAddress
+        * (layers.end - layers.start) as BufferAddress)
+}
+
 /// Function copied with some modifications from webgpu standard <https://gpuweb.github.io/gpuweb/#copy-between-buffer-texture>
 /// If successful, returns (number of buffer bytes required for this copy, number of bytes between array layers).
 pub(crate) fn validate_linear_texture_data(
@@ -172,6 +525,15 @@ pub(crate) fn validate_linear_texture_data(
     let width_in_blocks = copy_width / block_width;
     let height_in_blocks = copy_height / block_height;
 
+    // Reject sub-block extents before anything is derived from the block counts,
+    // a compressed copy less than a block high would have no rows at all.
+    if copy_width % block_width != 0 {
+        return Err(TransferError::UnalignedCopyWidth);
+    }
+    if copy_height % block_height != 0 {
+        return Err(TransferError::UnalignedCopyHeight);
+    }
+
     let bytes_per_row = if let Some(bytes_per_row) = layout.bytes_per_row {
         bytes_per_row.get() as BufferAddress
     } else {
@@ -190,13 +552,6 @@ pub(crate) fn validate_linear_texture_data(
     };
     let rows_per_image = block_rows_per_image * block_height;
 
-    if copy_width % block_width != 0 {
-        return Err(TransferError::UnalignedCopyWidth);
-    }
-    if copy_height % block_height != 0 {
-        return Err(TransferError::UnalignedCopyHeight);
-    }
-
     if need_copy_aligned_rows {
         let bytes_per_row_alignment = wgt::COPY_BYTES_PER_ROW_ALIGNMENT as BufferAddress;
 
@@ -213,14 +568,38 @@ pub(crate) fn validate_linear_texture_data(
     let required_bytes_in_copy = if copy_width == 0 || copy_height == 0 || copy_depth == 0 {
         0
     } else {
+        // `copy_height` is a non-zero multiple of the block height, so there is at least one row.
         let bytes_in_last_slice = bytes_per_row * (height_in_blocks - 1) + bytes_in_last_row;
         bytes_per_image * (copy_depth - 1) + bytes_in_last_slice
     };
 
     if rows_per_image < copy_height {
-        return Err(TransferError::InvalidRowsPerImage);
+        return Err(if copy_depth > 1 {
+            TransferError::InvalidRowsPerImageForLayers {
+                rows_per_image: rows_per_image as u32,
+                copy_height: copy_height as u32,
+                layers: copy_depth as u32,
+            }
+        } else {
+            TransferError::InvalidRowsPerImage
+        });
     }
     if offset + required_bytes_in_copy > buffer_size {
+        // Only the last row is unpadded, which is easy to miss when sizing
+        // a buffer, so call it out when it's the only row that doesn't fit.
+        if height_in_blocks > 1
+            && bytes_per_row > bytes_in_last_row
+            && offset + required_bytes_in_copy - bytes_in_last_row <= buffer_size
+        {
+            return Err(TransferError::BufferOverrunInLastRow {
+                start_offset: offset,
+                end_offset: offset + required_bytes_in_copy,
+                buffer_size,
+                bytes_per_row,
+                bytes_in_last_row,
+                side: buffer_side,
+            });
+        }
         return Err(TransferError::BufferOverrun {
             start_offset: offset,
             end_offset: offset + required_bytes_in_copy,
@@ -237,6 +616,149 @@ pub(crate) fn validate_linear_texture_data(
     Ok((required_bytes_in_copy, bytes_per_image))
 }
 
+/// Size of the linear data of a copy between a buffer and a texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutInfo {
+    /// Number of bytes of the buffer the copy touches, starting at the offset
+    /// of the layout. The last row isn't padded to the bytes per row.
+    pub required_bytes: BufferAddress,
+    /// Number of bytes between the images of the copy.
+    pub bytes_per_image: BufferAddress,
+}
+
+/// Validates `layout` for a copy of `extent` between a buffer of `buffer_size`
+/// bytes and the `aspect` of a texture of `format`, as a copy command on an
+/// encoder would.
+///
+/// This lets code building its own uploads check and size the linear data
+/// before recording a copy.
+pub fn check_image_data_layout(
+    layout: &wgt::ImageDataLayout,
+    format: wgt::TextureFormat,
+    buffer_size: BufferAddress,
+    extent: &Extent3d,
+    aspect: wgt::TextureAspect,
+) -> Result<LayoutInfo, TransferError> {
+    let aspect = texture_copy_aspect(format, aspect)?;
+    let (required_bytes, bytes_per_image) = validate_linear_texture_data(
+        layout,
+        format,
+        buffer_size,
+        CopySide::Source,
+        aspect_block_size(format, aspect) as BufferAddress,
+        extent,
+        true,
+    )?;
+    Ok(LayoutInfo {
+        required_bytes,
+        bytes_per_image,
+    })
+}
+
+/// Location of a single subresource of a texture in a buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubresourceLayout {
+    pub mip_level: u32,
+    pub array_layer: u32,
+    /// Offset of the first row in the buffer.
+    pub offset: BufferAddress,
+    pub bytes_per_row: u32,
+    /// Number of block rows in each image. Only 3D textures have more than
+    /// one image per subresource.
+    pub rows_per_image: u32,
+    /// Size of the subresource in texels, rounded up to whole blocks.
+    pub size: Extent3d,
+}
+
+/// Lays out all the subresources of a texture one after the other, starting at
+/// `offset`, with the rows aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+///
+/// The layouts are ordered by mip level, then by array layer. Returns them along
+/// with the end of the data of the last one.
+pub(crate) fn texture_readback_layouts(
+    desc: &wgt::TextureDescriptor<()>,
+    aspect: wgt::TextureAspect,
+    offset: BufferAddress,
+) -> Result<(Vec<SubresourceLayout>, BufferAddress), TransferError> {
+    let copy_aspect = texture_copy_aspect(desc.format, aspect)?;
+    let block_size = aspect_block_size(desc.format, copy_aspect) as u32;
+    let (block_width, block_height) = desc.format.describe().block_dimensions;
+    let layer_count = desc.array_layer_count();
+
+    let mut layouts = Vec::with_capacity((desc.mip_level_count * layer_count) as usize);
+    let mut mip_offset = offset;
+    let mut end = offset;
+    for mip_level in 0..desc.mip_level_count {
+        let copy_size = desc
+            .mip_level_size(mip_level)
+            .unwrap()
+            .physical_size(desc.format);
+        let bytes_in_row = copy_size.width / block_width as u32 * block_size;
+        let align = wgt::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = bytes_in_row + (align - bytes_in_row % align) % align;
+        let rows_per_image = copy_size.height / block_height as u32;
+        let layout = wgt::ImageDataLayout {
+            offset: mip_offset,
+            bytes_per_row: NonZeroU32::new(bytes_per_row),
+            rows_per_image: NonZeroU32::new(rows_per_image),
+        };
+        let (required_bytes_in_copy, bytes_per_image) = validate_linear_texture_data(
+            &layout,
+            desc.format,
+            BufferAddress::MAX,
+            CopySide::Destination,
+            block_size as BufferAddress,
+            &copy_size,
+            true,
+        )?;
+        let image_size = Extent3d {
+            depth_or_array_layers: copy_size.depth_or_array_layers / layer_count,
+            ..copy_size
+        };
+        layouts.extend((0..layer_count).map(|array_layer| SubresourceLayout {
+            mip_level,
+            array_layer,
+            offset: mip_offset + array_layer as BufferAddress * bytes_per_image,
+            bytes_per_row,
+            rows_per_image,
+            size: image_size,
+        }));
+        end = mip_offset + required_bytes_in_copy;
+        // Start the next level on a whole image, which keeps it aligned.
+        mip_offset += bytes_per_image * copy_size.depth_or_array_layers as BufferAddress;
+    }
+    Ok((layouts, end))
+}
+
+/// Checks that the rows described by `layout` hold the rows of the texture region
+/// of `copy_size`.
+///
+/// `validate_linear_texture_data` only compares the bytes per row against the
+/// row size when more than one row is copied, as the pitch doesn't matter to the
+/// buffer range then. The backends still copy with that pitch, so a layout
+/// narrower than the texture region doesn't describe the same copy.
+pub(crate) fn validate_linear_layout_covers_copy(
+    layout: &wgt::ImageDataLayout,
+    format: wgt::TextureFormat,
+    buffer_side: CopySide,
+    bytes_per_block: BufferAddress,
+    copy_size: &Extent3d,
+) -> Result<(), TransferError> {
+    let block_width = format.describe().block_dimensions.0 as u32;
+    let bytes_in_row = (copy_size.width / block_width) as BufferAddress * bytes_per_block;
+    match layout.bytes_per_row {
+        Some(bytes_per_row) if (bytes_per_row.get() as BufferAddress) < bytes_in_row => {
+            Err(TransferError::LayoutNarrowerThanCopy {
+                bytes_per_row: bytes_per_row.get() as BufferAddress,
+                bytes_in_row,
+                width: copy_size.width,
+                side: buffer_side,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Function copied with minor modifications from webgpu standard <https://gpuweb.github.io/gpuweb/#valid-texture-copy-range>
 /// Returns the HAL copy extent and the layer count.
 pub(crate) fn validate_texture_copy_range(
@@ -245,6 +767,14 @@ pub(crate) fn validate_texture_copy_range(
     texture_side: CopySide,
     copy_size: &Extent3d,
 ) -> Result<(hal::CopyExtent, u32), TransferError> {
+    // Checked ahead of the bounds, so that all the copies involving a 1D
+    // texture report it the same way.
+    if desc.dimension == wgt::TextureDimension::D1
+        && (copy_size.height != 1 || copy_size.depth_or_array_layers != 1)
+    {
+        return Err(TransferError::InvalidCopySize);
+    }
+
     let (block_width, block_height) = desc.format.describe().block_dimensions;
     let block_width = block_width as u32;
     let block_height = block_height as u32;
@@ -256,9 +786,12 @@ pub(crate) fn validate_texture_copy_range(
         },
     )?;
     // physical size can be larger than the virtual
+    // Note: `physical_size` only rounds up width and height to the block dimensions,
+    // so for D1/D2 textures the array layer count is carried through unchanged
+    // and the Z check below always validates against the full array count.
     let extent = extent_virtual.physical_size(desc.format);
 
-    let x_copy_max = texture_copy_view.origin.x + copy_size.width;
+    let x_copy_max = texture_copy_view.origin.x.saturating_add(copy_size.width);
     if x_copy_max > extent.width {
         return Err(TransferError::TextureOverrun {
             start_offset: texture_copy_view.origin.x,
@@ -268,7 +801,7 @@ pub(crate) fn validate_texture_copy_range(
             side: texture_side,
         });
     }
-    let y_copy_max = texture_copy_view.origin.y + copy_size.height;
+    let y_copy_max = texture_copy_view.origin.y.saturating_add(copy_size.height);
     if y_copy_max > extent.height {
         return Err(TransferError::TextureOverrun {
             start_offset: texture_copy_view.origin.y,
@@ -278,7 +811,10 @@ pub(crate) fn validate_texture_copy_range(
             side: texture_side,
         });
     }
-    let z_copy_max = texture_copy_view.origin.z + copy_size.depth_or_array_layers;
+    let z_copy_max = texture_copy_view
+        .origin
+        .z
+        .saturating_add(copy_size.depth_or_array_layers);
     if z_copy_max > extent.depth_or_array_layers {
         return Err(TransferError::TextureOverrun {
             start_offset: texture_copy_view.origin.z,
@@ -306,26 +842,60 @@ pub(crate) fn validate_texture_copy_range(
         wgt::TextureDimension::D1 | wgt::TextureDimension::D2 => {
             (1, copy_size.depth_or_array_layers)
         }
-        wgt::TextureDimension::D3 => (
-            copy_size
-                .depth_or_array_layers
-                .min(extent_virtual.depth_or_array_layers),
-            1,
-        ),
+        // The Z range of 3D textures is validated against the depth of the
+        // mip level above, so the copy covers exactly the requested slices.
+        wgt::TextureDimension::D3 => (copy_size.depth_or_array_layers, 1),
     };
 
     // WebGPU uses the physical size of the texture for copies whereas vulkan uses
     // the virtual size. We have passed validation, so it's safe to use the
     // image extent data directly. We want the provided copy size to be no larger than
-    // the virtual size.
+    // the virtual size, starting from the copy origin.
+    // The linear data is still laid out in whole blocks, as computed from `copy_size`.
+    // This doesn't under-copy the padding of compressed mips: the backends copy
+    // the whole blocks covering the extent, so a copy of the full physical size of
+    // a mip writes all of its blocks, including the texels past the virtual size.
     let copy_extent = hal::CopyExtent {
-        width: copy_size.width.min(extent_virtual.width),
-        height: copy_size.width.min(extent_virtual.height),
+        width: copy_size.width.min(
+            extent_virtual
+                .width
+                .saturating_sub(texture_copy_view.origin.x),
+        ),
+        height: copy_size.height.min(
+            extent_virtual
+                .height
+                .saturating_sub(texture_copy_view.origin.y),
+        ),
         depth,
     };
     Ok((copy_extent, array_layer_count))
 }
 
+/// Validates both sides of a texture to texture copy, and returns the extent
+/// that is copied, along with the number of array layers.
+///
+/// A copy size exceeding the mip level extent of either side is a `TextureOverrun`.
+/// The only shrinking done is to the virtual size of each mip level,
+/// when the copy covers the padding of the last block of a compressed texture.
+pub(crate) fn validate_texture_to_texture_copy_range(
+    source: &ImageCopyTexture,
+    src_desc: &wgt::TextureDescriptor<()>,
+    destination: &ImageCopyTexture,
+    dst_desc: &wgt::TextureDescriptor<()>,
+    copy_size: &Extent3d,
+) -> Result<(hal::CopyExtent, u32), TransferError> {
+    let (src_copy_size, array_layer_count) =
+        validate_texture_copy_range(source, src_desc, CopySide::Source, copy_size)?;
+    let (dst_copy_size, _) =
+        validate_texture_copy_range(destination, dst_desc, CopySide::Destination, copy_size)?;
+    let copy_extent = hal::CopyExtent {
+        width: src_copy_size.width.min(dst_copy_size.width),
+        height: src_copy_size.height.min(dst_copy_size.height),
+        depth: src_copy_size.depth.min(dst_copy_size.depth),
+    };
+    Ok((copy_extent, array_layer_count))
+}
+
 impl<G: GlobalIdentityHandlerFactory> Global<G> {
     pub fn command_encoder_copy_buffer_to_buffer<A: HalApi>(
         &self,
@@ -336,8 +906,36 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         destination_offset: BufferAddress,
         size: BufferAddress,
     ) -> Result<(), CopyError> {
+        self.command_encoder_copy_buffer_to_buffer_sized::<A>(
+            command_encoder_id,
+            source,
+            source_offset,
+            destination,
+            destination_offset,
+            size,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `command_encoder_copy_buffer_to_buffer`, but also returns
+    /// the number of bytes the copy transfers.
+    pub fn command_encoder_copy_buffer_to_buffer_sized<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: BufferId,
+        source_offset: BufferAddress,
+        destination: BufferId,
+        destination_offset: BufferAddress,
+        size: BufferAddress,
+    ) -> Result<CopyStats, CopyError> {
         profiling::scope!("copy_buffer_to_buffer", "CommandEncoder");
 
+        // WebGPU requires the source and destination to be different buffers.
+        // Allowing a copy within a buffer would need the tracker to keep the buffer
+        // in a combined `COPY_SRC | COPY_DST` state for the copy, instead of the
+        // second `use_replace` silently overriding the first. Backends would then
+        // need a single state for that, and D3D12 can't combine a read state with
+        // a write one, so it would have to copy through a temporary buffer.
         if source == destination {
             return Err(TransferError::SameSourceDestinationBuffer.into());
         }
@@ -371,6 +969,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         if !src_buffer.usage.contains(BufferUsages::COPY_SRC) {
             return Err(TransferError::MissingCopySrcUsageFlag.into());
         }
+        validate_source_buffer_unmapped(&src_buffer.map_state, source)?;
         // expecting only a single barrier
         let src_barrier = src_pending
             .map(|pending| pending.into_hal(src_buffer))
@@ -402,43 +1001,46 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             return Err(TransferError::UnalignedBufferOffset(destination_offset).into());
         }
 
-        let source_end_offset = source_offset + size;
-        let destination_end_offset = destination_offset + size;
-        if source_end_offset > src_buffer.size {
-            return Err(TransferError::BufferOverrun {
-                start_offset: source_offset,
-                end_offset: source_end_offset,
-                buffer_size: src_buffer.size,
-                side: CopySide::Source,
-            }
-            .into());
-        }
-        if destination_end_offset > dst_buffer.size {
-            return Err(TransferError::BufferOverrun {
-                start_offset: destination_offset,
-                end_offset: destination_end_offset,
-                buffer_size: dst_buffer.size,
-                side: CopySide::Destination,
-            }
-            .into());
+        let source_end_offset =
+            validate_buffer_range(source_offset, size, src_buffer.size, CopySide::Source)?;
+        let destination_end_offset = validate_buffer_range(
+            destination_offset,
+            size,
+            dst_buffer.size,
+            CopySide::Destination,
+        )?;
+
+        if buffer_ranges_alias(
+            src_buffer,
+            source_offset..source_end_offset,
+            dst_buffer,
+            destination_offset..destination_end_offset,
+        ) {
+            return Err(TransferError::OverlappingCopyRange.into());
         }
 
         if size == 0 {
             log::trace!("Ignoring copy_buffer_to_buffer of size 0");
-            return Ok(());
+            return Ok(CopyStats::default());
         }
 
         // Make sure source is initialized memory and mark dest as initialized.
-        cmd_buf.buffer_memory_init_actions.extend(
-            dst_buffer
-                .initialization_status
-                .check(destination_offset..(destination_offset + size))
-                .map(|range| MemoryInitTrackerAction {
+        // The destination can't be marked right away, since the command buffer
+        // may never be submitted, or may read the buffer before this copy. A copy
+        // overwriting all of it records the whole range instead, so that the
+        // submission can mark it initialized in one go.
+        if let Some(range) = dst_buffer
+            .initialization_status
+            .check_write(destination_offset..destination_end_offset, dst_buffer.size)
+        {
+            cmd_buf
+                .buffer_memory_init_actions
+                .push(MemoryInitTrackerAction {
                     id: destination,
                     range,
                     kind: MemoryInitKind::ImplicitlyInitialized,
-                }),
-        );
+                });
+        }
         cmd_buf.buffer_memory_init_actions.extend(
             src_buffer
                 .initialization_status
@@ -455,12 +1057,24 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             dst_offset: destination_offset,
             size: wgt::BufferSize::new(size).unwrap(),
         };
+        let marker = copy_debug_marker(cmd_buf.copy_debug_markers, || {
+            format!(
+                "copy_buffer_to_buffer {:?} -> {:?}, {} bytes",
+                source, destination, size
+            )
+        });
         let cmd_buf_raw = cmd_buf.encoder.open();
         unsafe {
+            if let Some(ref marker) = marker {
+                cmd_buf_raw.begin_debug_marker(marker);
+            }
             cmd_buf_raw.transition_buffers(src_barrier.into_iter().chain(dst_barrier));
             cmd_buf_raw.copy_buffer_to_buffer(src_raw, dst_raw, iter::once(region));
+            if marker.is_some() {
+                cmd_buf_raw.end_debug_marker();
+            }
         }
-        Ok(())
+        Ok(CopyStats::new(size))
     }
 
     pub fn command_encoder_copy_buffer_to_texture<A: HalApi>(
@@ -470,6 +1084,24 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         destination: &ImageCopyTexture,
         copy_size: &Extent3d,
     ) -> Result<(), CopyError> {
+        self.command_encoder_copy_buffer_to_texture_sized::<A>(
+            command_encoder_id,
+            source,
+            destination,
+            copy_size,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `command_encoder_copy_buffer_to_texture`, but also returns
+    /// the number of bytes the copy transfers.
+    pub fn command_encoder_copy_buffer_to_texture_sized<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyBuffer,
+        destination: &ImageCopyTexture,
+        copy_size: &Extent3d,
+    ) -> Result<CopyStats, CopyError> {
         profiling::scope!("copy_buffer_to_texture", "CommandEncoder");
 
         let hub = A::hub(self);
@@ -491,11 +1123,15 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
 
         if copy_size.width == 0 || copy_size.height == 0 || copy_size.depth_or_array_layers == 0 {
             log::trace!("Ignoring copy_buffer_to_texture of size 0");
-            return Ok(());
+            return Ok(CopyStats::default());
         }
 
-        let (dst_range, dst_base, _) =
-            extract_texture_selector(destination, copy_size, &*texture_guard)?;
+        let (dst_range, dst_base, _) = extract_texture_selector(
+            destination,
+            copy_size,
+            CopySide::Destination,
+            &*texture_guard,
+        )?;
 
         let (src_buffer, src_pending) = cmd_buf
             .trackers
@@ -509,8 +1145,13 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         if !src_buffer.usage.contains(BufferUsages::COPY_SRC) {
             return Err(TransferError::MissingCopySrcUsageFlag.into());
         }
+        validate_source_buffer_unmapped(&src_buffer.map_state, source.buffer)?;
         let src_barriers = src_pending.map(|pending| pending.into_hal(src_buffer));
 
+        // Attachment usage is scoped to render passes, which are closed by now,
+        // so a texture used as an attachment earlier in this command buffer is
+        // simply transitioned here. Copies only conflict with themselves, see
+        // `validate_copy_subresources`.
         let (dst_texture, dst_pending) = cmd_buf
             .trackers
             .textures
@@ -577,23 +1218,45 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 size: hal_copy_size,
             }
         });
+        let marker = copy_debug_marker(cmd_buf.copy_debug_markers, || {
+            format!(
+                "copy_buffer_to_texture {:?} -> {:?}, {}x{}x{}",
+                source.buffer,
+                destination.texture,
+                copy_size.width,
+                copy_size.height,
+                copy_size.depth_or_array_layers
+            )
+        });
         let cmd_buf_raw = cmd_buf.encoder.open();
         unsafe {
+            if let Some(ref marker) = marker {
+                cmd_buf_raw.begin_debug_marker(marker);
+            }
             cmd_buf_raw.transition_buffers(src_barriers);
             cmd_buf_raw.transition_textures(dst_barriers);
             cmd_buf_raw.copy_buffer_to_texture(src_raw, dst_raw, regions);
+            if marker.is_some() {
+                cmd_buf_raw.end_debug_marker();
+            }
         }
-        Ok(())
+        Ok(CopyStats::new(required_buffer_bytes_in_copy))
     }
 
-    pub fn command_encoder_copy_texture_to_buffer<A: HalApi>(
+    /// Copies a whole mip chain from `source_buffer` into `destination`.
+    ///
+    /// Mip level `i` is copied in its entirety, including all array layers,
+    /// from the location in `source_buffer` described by `mip_layouts[i]`.
+    /// All the levels are recorded as a single copy command.
+    pub fn command_encoder_upload_mip_chain<A: HalApi>(
         &self,
         command_encoder_id: CommandEncoderId,
-        source: &ImageCopyTexture,
-        destination: &ImageCopyBuffer,
-        copy_size: &Extent3d,
+        source_buffer: BufferId,
+        mip_layouts: &[wgt::ImageDataLayout],
+        destination: TextureId,
+        aspect: wgt::TextureAspect,
     ) -> Result<(), CopyError> {
-        profiling::scope!("copy_texture_to_buffer", "CommandEncoder");
+        profiling::scope!("upload_mip_chain", "CommandEncoder");
 
         let hub = A::hub(self);
         let mut token = Token::root();
@@ -603,48 +1266,406 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         let (buffer_guard, mut token) = hub.buffers.read(&mut token);
         let (texture_guard, _) = hub.textures.read(&mut token);
 
+        let dst_desc = &texture_guard
+            .get(destination)
+            .map_err(|_| TransferError::InvalidTexture(destination))?
+            .desc;
+        let mip_count = mip_layouts.len() as u32;
+        if mip_count > dst_desc.mip_level_count {
+            return Err(TransferError::InvalidTextureMipLevel {
+                level: mip_count - 1,
+                total: dst_desc.mip_level_count,
+            }
+            .into());
+        }
+
+        let copies = mip_layouts
+            .iter()
+            .enumerate()
+            .map(|(level, layout)| {
+                let mip_level = level as u32;
+                let copy_size = dst_desc
+                    .mip_level_size(mip_level)
+                    .unwrap()
+                    .physical_size(dst_desc.format);
+                let src = ImageCopyBuffer {
+                    buffer: source_buffer,
+                    layout: *layout,
+                };
+                let dst = ImageCopyTexture {
+                    texture: destination,
+                    mip_level,
+                    origin: wgt::Origin3d::ZERO,
+                    aspect,
+                };
+                (src, dst, copy_size)
+            })
+            .collect::<Vec<_>>();
+
         #[cfg(feature = "trace")]
         if let Some(ref mut list) = cmd_buf.commands {
-            list.push(TraceCommand::CopyTextureToBuffer {
-                src: source.clone(),
-                dst: destination.clone(),
-                size: *copy_size,
-            });
+            for &(ref src, ref dst, size) in copies.iter() {
+                list.push(TraceCommand::CopyBufferToTexture {
+                    src: src.clone(),
+                    dst: dst.clone(),
+                    size,
+                });
+            }
         }
 
-        if copy_size.width == 0 || copy_size.height == 0 || copy_size.depth_or_array_layers == 0 {
-            log::trace!("Ignoring copy_texture_to_buffer of size 0");
+        if copies.is_empty() {
+            log::trace!("Ignoring upload_mip_chain without mip levels");
             return Ok(());
         }
 
-        let (src_range, src_base, _) =
-            extract_texture_selector(source, copy_size, &*texture_guard)?;
+        let dst_range = TextureSelector {
+            levels: 0..mip_count,
+            layers: 0..dst_desc.array_layer_count(),
+        };
 
-        let (src_texture, src_pending) = cmd_buf
+        let (src_buffer, src_pending) = cmd_buf
+            .trackers
+            .buffers
+            .use_replace(&*buffer_guard, source_buffer, (), hal::BufferUses::COPY_SRC)
+            .map_err(TransferError::InvalidBuffer)?;
+        let src_raw = src_buffer
+            .raw
+            .as_ref()
+            .ok_or(TransferError::InvalidBuffer(source_buffer))?;
+        if !src_buffer.usage.contains(BufferUsages::COPY_SRC) {
+            return Err(TransferError::MissingCopySrcUsageFlag.into());
+        }
+        validate_source_buffer_unmapped(&src_buffer.map_state, source_buffer)?;
+        let src_barriers = src_pending.map(|pending| pending.into_hal(src_buffer));
+
+        let (dst_texture, dst_pending) = cmd_buf
             .trackers
             .textures
             .use_replace(
                 &*texture_guard,
-                source.texture,
-                src_range,
-                hal::TextureUses::COPY_SRC,
+                destination,
+                dst_range,
+                hal::TextureUses::COPY_DST,
             )
             .unwrap();
-        let src_raw = src_texture
+        let dst_raw = dst_texture
             .raw
             .as_ref()
-            .ok_or(TransferError::InvalidTexture(source.texture))?;
-        if !src_texture.desc.usage.contains(TextureUsages::COPY_SRC) {
-            return Err(TransferError::MissingCopySrcUsageFlag.into());
+            .ok_or(TransferError::InvalidTexture(destination))?;
+        if !dst_texture.desc.usage.contains(TextureUsages::COPY_DST) {
+            return Err(TransferError::MissingCopyDstUsageFlag(None, Some(destination)).into());
         }
-        let src_barriers = src_pending.map(|pending| pending.into_hal(src_texture));
+        let dst_barriers = dst_pending.map(|pending| pending.into_hal(dst_texture));
 
-        let (dst_buffer, dst_pending) = cmd_buf
-            .trackers
-            .buffers
-            .use_replace(
-                &*buffer_guard,
-                destination.buffer,
+        if !conv::is_valid_copy_dst_texture_format(dst_texture.desc.format) {
+            return Err(
+                TransferError::CopyToForbiddenTextureFormat(dst_texture.desc.format).into(),
+            );
+        }
+
+        let format_desc = dst_texture.desc.format.describe();
+        let mut regions = Vec::new();
+        for (src, dst, copy_size) in copies.iter() {
+            let (_, dst_base, _) =
+                extract_texture_selector(dst, copy_size, CopySide::Destination, &*texture_guard)?;
+            let (hal_copy_size, array_layer_count) = validate_texture_copy_range(
+                dst,
+                &dst_texture.desc,
+                CopySide::Destination,
+                copy_size,
+            )?;
+            let (required_buffer_bytes_in_copy, bytes_per_array_layer) =
+                validate_linear_texture_data(
+                    &src.layout,
+                    dst_texture.desc.format,
+                    src_buffer.size,
+                    CopySide::Source,
+                    format_desc.block_size as BufferAddress,
+                    copy_size,
+                    true,
+                )?;
+
+            cmd_buf.buffer_memory_init_actions.extend(
+                src_buffer
+                    .initialization_status
+                    .check(src.layout.offset..(src.layout.offset + required_buffer_bytes_in_copy))
+                    .map(|range| MemoryInitTrackerAction {
+                        id: source_buffer,
+                        range,
+                        kind: MemoryInitKind::NeedsInitializedMemory,
+                    }),
+            );
+
+            regions.extend((0..array_layer_count).map(|rel_array_layer| {
+                let mut texture_base = dst_base.clone();
+                texture_base.array_layer += rel_array_layer;
+                let mut buffer_layout = src.layout;
+                buffer_layout.offset += rel_array_layer as u64 * bytes_per_array_layer;
+                hal::BufferTextureCopy {
+                    buffer_layout,
+                    texture_base,
+                    size: hal_copy_size,
+                }
+            }));
+        }
+
+        let marker = copy_debug_marker(cmd_buf.copy_debug_markers, || {
+            format!(
+                "upload_mip_chain {:?} -> {:?}, {} mips",
+                source_buffer,
+                destination,
+                mip_layouts.len()
+            )
+        });
+        let cmd_buf_raw = cmd_buf.encoder.open();
+        unsafe {
+            if let Some(ref marker) = marker {
+                cmd_buf_raw.begin_debug_marker(marker);
+            }
+            cmd_buf_raw.transition_buffers(src_barriers);
+            cmd_buf_raw.transition_textures(dst_barriers);
+            cmd_buf_raw.copy_buffer_to_texture(src_raw, dst_raw, regions.into_iter());
+            if marker.is_some() {
+                cmd_buf_raw.end_debug_marker();
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies every subresource of `source` into `destination`, one after the
+    /// other starting at `offset`, and returns where each of them ends up.
+    ///
+    /// The rows of each subresource are aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`,
+    /// as `command_encoder_copy_texture_to_buffer` requires. The whole layout is
+    /// checked to fit in the buffer before anything is recorded.
+    pub fn command_encoder_copy_texture_all_to_buffer<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: TextureId,
+        aspect: wgt::TextureAspect,
+        destination: BufferId,
+        offset: BufferAddress,
+    ) -> Result<Vec<SubresourceLayout>, CopyError> {
+        let hub = A::hub(self);
+        let (desc, layouts) = {
+            let mut token = Token::root();
+            let (buffer_guard, mut token) = hub.buffers.read(&mut token);
+            let (texture_guard, _) = hub.textures.read(&mut token);
+            let texture = texture_guard
+                .get(source)
+                .map_err(|_| TransferError::InvalidTexture(source))?;
+            let buffer = buffer_guard
+                .get(destination)
+                .map_err(|_| TransferError::InvalidBuffer(destination))?;
+
+            let (layouts, end) = texture_readback_layouts(&texture.desc, aspect, offset)?;
+            if end > buffer.size {
+                return Err(TransferError::BufferOverrun {
+                    start_offset: offset,
+                    end_offset: end,
+                    buffer_size: buffer.size,
+                    side: CopySide::Destination,
+                }
+                .into());
+            }
+            (texture.desc.clone(), layouts)
+        };
+
+        // The layers of a level are laid out consecutively, so each level is a single copy.
+        let layer_count = desc.array_layer_count();
+        for mip_level in 0..desc.mip_level_count {
+            let first = &layouts[(mip_level * layer_count) as usize];
+            let copy_size = desc
+                .mip_level_size(mip_level)
+                .unwrap()
+                .physical_size(desc.format);
+            self.copy_texture_to_buffer_impl::<A>(
+                command_encoder_id,
+                &ImageCopyTexture {
+                    texture: source,
+                    mip_level,
+                    origin: wgt::Origin3d::ZERO,
+                    aspect,
+                },
+                &ImageCopyBuffer {
+                    buffer: destination,
+                    layout: wgt::ImageDataLayout {
+                        offset: first.offset,
+                        bytes_per_row: NonZeroU32::new(first.bytes_per_row),
+                        rows_per_image: NonZeroU32::new(first.rows_per_image),
+                    },
+                },
+                &copy_size,
+                true,
+            )?;
+        }
+        Ok(layouts)
+    }
+
+    pub fn command_encoder_copy_texture_to_buffer<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyTexture,
+        destination: &ImageCopyBuffer,
+        copy_size: &Extent3d,
+    ) -> Result<(), CopyError> {
+        self.command_encoder_copy_texture_to_buffer_sized::<A>(
+            command_encoder_id,
+            source,
+            destination,
+            copy_size,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `command_encoder_copy_texture_to_buffer`, but also returns
+    /// the number of bytes the copy transfers.
+    pub fn command_encoder_copy_texture_to_buffer_sized<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyTexture,
+        destination: &ImageCopyBuffer,
+        copy_size: &Extent3d,
+    ) -> Result<CopyStats, CopyError> {
+        self.copy_texture_to_buffer_impl::<A>(
+            command_encoder_id,
+            source,
+            destination,
+            copy_size,
+            true,
+        )
+    }
+
+    /// Copy a texture region into `buffer` at `offset`, with the rows tightly
+    /// packed if the device is able to copy them that way.
+    ///
+    /// Returns the layout of the copied data in the buffer. If the device
+    /// requires a bigger row pitch for the format, the rows are padded to
+    /// `COPY_BYTES_PER_ROW_ALIGNMENT` instead, as with
+    /// `command_encoder_copy_texture_to_buffer`, and it's up to the caller
+    /// to unpad them after reading the buffer back.
+    pub fn command_encoder_copy_texture_to_buffer_tight<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyTexture,
+        buffer: BufferId,
+        offset: BufferAddress,
+        copy_size: &Extent3d,
+    ) -> Result<wgt::ImageDataLayout, CopyError> {
+        let hub = A::hub(self);
+        let (bytes_per_row, rows_per_image) = {
+            let mut token = Token::root();
+            let (device_guard, mut token) = hub.devices.read(&mut token);
+            let (mut cmd_buf_guard, mut token) = hub.command_buffers.write(&mut token);
+            let cmd_buf = CommandBuffer::get_encoder_mut(&mut *cmd_buf_guard, command_encoder_id)?;
+            let device = &device_guard[cmd_buf.device_id.value];
+            let (_, mut token) = hub.buffers.read(&mut token);
+            let (texture_guard, _) = hub.textures.read(&mut token);
+            let texture = texture_guard
+                .get(source.texture)
+                .map_err(|_| TransferError::InvalidTexture(source.texture))?;
+
+            let format = texture.desc.format;
+            let aspect = texture_copy_aspect(format, source.aspect)?;
+            let (block_width, block_height) = format.describe().block_dimensions;
+            let block_size = aspect_block_size(format, aspect) as u32;
+            let tight_bytes_per_row = copy_size.width / block_width as u32 * block_size;
+            let bytes_per_row =
+                if tight_bytes_per_row % device.alignments.buffer_copy_pitch.get() as u32 == 0 {
+                    tight_bytes_per_row
+                } else {
+                    let align = wgt::COPY_BYTES_PER_ROW_ALIGNMENT;
+                    tight_bytes_per_row + (align - tight_bytes_per_row % align) % align
+                };
+            (bytes_per_row, copy_size.height / block_height as u32)
+        };
+
+        let destination = ImageCopyBuffer {
+            buffer,
+            layout: wgt::ImageDataLayout {
+                offset,
+                bytes_per_row: NonZeroU32::new(bytes_per_row),
+                rows_per_image: NonZeroU32::new(rows_per_image),
+            },
+        };
+        self.copy_texture_to_buffer_impl::<A>(
+            command_encoder_id,
+            source,
+            &destination,
+            copy_size,
+            false,
+        )?;
+        Ok(destination.layout)
+    }
+
+    fn copy_texture_to_buffer_impl<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyTexture,
+        destination: &ImageCopyBuffer,
+        copy_size: &Extent3d,
+        need_copy_aligned_rows: bool,
+    ) -> Result<CopyStats, CopyError> {
+        profiling::scope!("copy_texture_to_buffer", "CommandEncoder");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+
+        let (mut cmd_buf_guard, mut token) = hub.command_buffers.write(&mut token);
+        let cmd_buf = CommandBuffer::get_encoder_mut(&mut *cmd_buf_guard, command_encoder_id)?;
+        let (buffer_guard, mut token) = hub.buffers.read(&mut token);
+        let (texture_guard, _) = hub.textures.read(&mut token);
+
+        #[cfg(feature = "trace")]
+        if let Some(ref mut list) = cmd_buf.commands {
+            list.push(if need_copy_aligned_rows {
+                TraceCommand::CopyTextureToBuffer {
+                    src: source.clone(),
+                    dst: destination.clone(),
+                    size: *copy_size,
+                }
+            } else {
+                TraceCommand::CopyTextureToBufferTight {
+                    src: source.clone(),
+                    dst: destination.clone(),
+                    size: *copy_size,
+                }
+            });
+        }
+
+        if copy_size.width == 0 || copy_size.height == 0 || copy_size.depth_or_array_layers == 0 {
+            log::trace!("Ignoring copy_texture_to_buffer of size 0");
+            return Ok(CopyStats::default());
+        }
+
+        let (src_range, src_base, _) =
+            extract_texture_selector(source, copy_size, CopySide::Source, &*texture_guard)?;
+
+        let (src_texture, src_pending) = cmd_buf
+            .trackers
+            .textures
+            .use_replace(
+                &*texture_guard,
+                source.texture,
+                src_range,
+                hal::TextureUses::COPY_SRC,
+            )
+            .unwrap();
+        let src_raw = src_texture
+            .raw
+            .as_ref()
+            .ok_or(TransferError::InvalidTexture(source.texture))?;
+        if !src_texture.desc.usage.contains(TextureUsages::COPY_SRC) {
+            return Err(TransferError::MissingCopySrcUsageFlag.into());
+        }
+        let src_barriers = src_pending.map(|pending| pending.into_hal(src_texture));
+
+        let (dst_buffer, dst_pending) = cmd_buf
+            .trackers
+            .buffers
+            .use_replace(
+                &*buffer_guard,
+                destination.buffer,
                 (),
                 hal::BufferUses::COPY_DST,
             )
@@ -660,7 +1681,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         }
         let dst_barriers = dst_pending.map(|pending| pending.into_hal(dst_buffer));
 
-        let format_desc = src_texture.desc.format.describe();
+        let block_size = aspect_block_size(src_texture.desc.format, src_base.aspect);
         let (hal_copy_size, array_layer_count) =
             validate_texture_copy_range(source, &src_texture.desc, CopySide::Source, copy_size)?;
         let (required_buffer_bytes_in_copy, bytes_per_array_layer) = validate_linear_texture_data(
@@ -668,9 +1689,16 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             src_texture.desc.format,
             dst_buffer.size,
             CopySide::Destination,
-            format_desc.block_size as BufferAddress,
+            block_size as BufferAddress,
+            copy_size,
+            need_copy_aligned_rows,
+        )?;
+        validate_linear_layout_covers_copy(
+            &destination.layout,
+            src_texture.desc.format,
+            CopySide::Destination,
+            block_size as BufferAddress,
             copy_size,
-            true,
         )?;
 
         if !conv::is_valid_copy_src_texture_format(src_texture.desc.format) {
@@ -704,8 +1732,21 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 size: hal_copy_size,
             }
         });
+        let marker = copy_debug_marker(cmd_buf.copy_debug_markers, || {
+            format!(
+                "copy_texture_to_buffer {:?} -> {:?}, {}x{}x{}",
+                source.texture,
+                destination.buffer,
+                copy_size.width,
+                copy_size.height,
+                copy_size.depth_or_array_layers
+            )
+        });
         let cmd_buf_raw = cmd_buf.encoder.open();
         unsafe {
+            if let Some(ref marker) = marker {
+                cmd_buf_raw.begin_debug_marker(marker);
+            }
             cmd_buf_raw.transition_buffers(dst_barriers);
             cmd_buf_raw.transition_textures(src_barriers);
             cmd_buf_raw.copy_texture_to_buffer(
@@ -714,8 +1755,11 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 dst_raw,
                 regions,
             );
+            if marker.is_some() {
+                cmd_buf_raw.end_debug_marker();
+            }
         }
-        Ok(())
+        Ok(CopyStats::new(required_buffer_bytes_in_copy))
     }
 
     pub fn command_encoder_copy_texture_to_texture<A: HalApi>(
@@ -725,6 +1769,24 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         destination: &ImageCopyTexture,
         copy_size: &Extent3d,
     ) -> Result<(), CopyError> {
+        self.command_encoder_copy_texture_to_texture_sized::<A>(
+            command_encoder_id,
+            source,
+            destination,
+            copy_size,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `command_encoder_copy_texture_to_texture`, but also returns
+    /// the number of bytes the copy transfers.
+    pub fn command_encoder_copy_texture_to_texture_sized<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: &ImageCopyTexture,
+        destination: &ImageCopyTexture,
+        copy_size: &Extent3d,
+    ) -> Result<CopyStats, CopyError> {
         profiling::scope!("copy_texture_to_texture", "CommandEncoder");
 
         let hub = A::hub(self);
@@ -746,16 +1808,21 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
 
         if copy_size.width == 0 || copy_size.height == 0 || copy_size.depth_or_array_layers == 0 {
             log::trace!("Ignoring copy_texture_to_texture of size 0");
-            return Ok(());
+            return Ok(CopyStats::default());
         }
 
-        let (src_range, src_tex_base, _) =
-            extract_texture_selector(source, copy_size, &*texture_guard)?;
-        let (dst_range, dst_tex_base, _) =
-            extract_texture_selector(destination, copy_size, &*texture_guard)?;
-        if src_tex_base.aspect != dst_tex_base.aspect {
-            return Err(TransferError::MismatchedAspects.into());
-        }
+        let (src_range, src_tex_base, src_format) =
+            extract_texture_selector(source, copy_size, CopySide::Source, &*texture_guard)?;
+        let (dst_range, dst_tex_base, dst_format) = extract_texture_selector(
+            destination,
+            copy_size,
+            CopySide::Destination,
+            &*texture_guard,
+        )?;
+        validate_texture_copy_formats(src_format, dst_format)?;
+        // The bases carry the copied aspect down to the HAL copy.
+        texture_to_texture_copy_aspect(src_format, source.aspect, dst_format, destination.aspect)?;
+        validate_copy_subresources(source.texture, &src_range, destination.texture, &dst_range)?;
 
         let (src_texture, src_pending) = cmd_buf
             .trackers
@@ -801,20 +1868,21 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         }
         barriers.extend(dst_pending.map(|pending| pending.into_hal(dst_texture)));
 
-        let (src_copy_size, array_layer_count) =
-            validate_texture_copy_range(source, &src_texture.desc, CopySide::Source, copy_size)?;
-        let (dst_copy_size, _) = validate_texture_copy_range(
+        let (hal_copy_size, array_layer_count) = validate_texture_to_texture_copy_range(
+            source,
+            &src_texture.desc,
             destination,
             &dst_texture.desc,
-            CopySide::Destination,
             copy_size,
         )?;
 
-        let hal_copy_size = hal::CopyExtent {
-            width: src_copy_size.width.min(dst_copy_size.width),
-            height: src_copy_size.height.min(dst_copy_size.height),
-            depth: src_copy_size.depth.min(dst_copy_size.depth),
-        };
+        let format_desc = src_texture.desc.format.describe();
+        let (block_width, block_height) = format_desc.block_dimensions;
+        let bytes = (copy_size.width / block_width as u32) as BufferAddress
+            * (copy_size.height / block_height as u32) as BufferAddress
+            * copy_size.depth_or_array_layers as BufferAddress
+            * aspect_block_size(src_texture.desc.format, src_tex_base.aspect) as BufferAddress;
+
         let regions = (0..array_layer_count).map(|rel_array_layer| {
             let mut src_base = src_tex_base.clone();
             let mut dst_base = dst_tex_base.clone();
@@ -826,8 +1894,21 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 size: hal_copy_size,
             }
         });
+        let marker = copy_debug_marker(cmd_buf.copy_debug_markers, || {
+            format!(
+                "copy_texture_to_texture {:?} -> {:?}, {}x{}x{}",
+                source.texture,
+                destination.texture,
+                copy_size.width,
+                copy_size.height,
+                copy_size.depth_or_array_layers
+            )
+        });
         let cmd_buf_raw = cmd_buf.encoder.open();
         unsafe {
+            if let Some(ref marker) = marker {
+                cmd_buf_raw.begin_debug_marker(marker);
+            }
             cmd_buf_raw.transition_textures(barriers.into_iter());
             cmd_buf_raw.copy_texture_to_texture(
                 src_raw,
@@ -835,7 +1916,1311 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 dst_raw,
                 regions,
             );
+            if marker.is_some() {
+                cmd_buf_raw.end_debug_marker();
+            }
+        }
+        Ok(CopyStats::new(bytes))
+    }
+
+    /// Returns the minimal size of a buffer that can receive a copy of the
+    /// given texture region with `command_encoder_copy_texture_to_buffer`,
+    /// assuming the rows are tightly packed up to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+    pub fn texture_readback_buffer_size<A: HalApi>(
+        &self,
+        texture_id: TextureId,
+        mip_level: u32,
+        origin: wgt::Origin3d,
+        extent: &Extent3d,
+        aspect: wgt::TextureAspect,
+    ) -> Result<BufferAddress, TransferError> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (texture_guard, _) = hub.textures.read(&mut token);
+        let texture = texture_guard
+            .get(texture_id)
+            .map_err(|_| TransferError::InvalidTexture(texture_id))?;
+
+        if extent.width == 0 || extent.height == 0 || extent.depth_or_array_layers == 0 {
+            return Ok(0);
+        }
+
+        let source = ImageCopyTexture {
+            texture: texture_id,
+            mip_level,
+            origin,
+            aspect,
+        };
+        let (_, base, _) =
+            extract_texture_selector(&source, extent, CopySide::Source, &*texture_guard)?;
+        validate_texture_copy_range(&source, &texture.desc, CopySide::Source, extent)?;
+        if !conv::is_valid_copy_src_texture_format(texture.desc.format) {
+            return Err(TransferError::CopyFromForbiddenTextureFormat(
+                texture.desc.format,
+            ));
+        }
+
+        let (block_width, block_height) = texture.desc.format.describe().block_dimensions;
+        let block_size = aspect_block_size(texture.desc.format, base.aspect);
+        let width_in_blocks = extent.width / block_width as u32;
+        let height_in_blocks = extent.height / block_height as u32;
+        let unpadded_bytes_per_row = width_in_blocks * block_size as u32;
+        let align = wgt::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row =
+            unpadded_bytes_per_row + (align - unpadded_bytes_per_row % align) % align;
+        let layout = wgt::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(bytes_per_row),
+            rows_per_image: NonZeroU32::new(height_in_blocks),
+        };
+        let (required_buffer_bytes_in_copy, _) = validate_linear_texture_data(
+            &layout,
+            texture.desc.format,
+            BufferAddress::MAX,
+            CopySide::Destination,
+            block_size as BufferAddress,
+            extent,
+            true,
+        )?;
+        Ok(required_buffer_bytes_in_copy)
+    }
+
+    /// Returns the size of `layers` of mip `mip_level` of the given texture,
+    /// with the rows tightly packed. Unlike `texture_readback_buffer_size`,
+    /// no row alignment is applied.
+    pub fn texture_subresource_size<A: HalApi>(
+        &self,
+        texture_id: TextureId,
+        mip_level: u32,
+        layers: Range<u32>,
+        aspect: wgt::TextureAspect,
+    ) -> Result<BufferAddress, TransferError> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (texture_guard, _) = hub.textures.read(&mut token);
+        let texture = texture_guard
+            .get(texture_id)
+            .map_err(|_| TransferError::InvalidTexture(texture_id))?;
+
+        let aspect = texture_copy_aspect(texture.desc.format, aspect)?;
+        texture_subresource_size(&texture.desc, mip_level, layers, aspect)
+    }
+
+    /// Returns the memory layout of the given texture, as reported by the backend.
+    ///
+    /// Vulkan reports the tiling the image was created with, and DX12 the
+    /// layout of the resource. Both create the textures in the optimal layout
+    /// of the GPU, and copies from buffers convert the rows into it. Vulkan
+    /// swap chain images, Metal and GL textures are reported as unknown, since
+    /// their layout isn't exposed.
+    pub fn texture_tiling<A: HalApi>(
+        &self,
+        texture_id: TextureId,
+    ) -> Result<TextureTiling, TransferError> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, mut token) = hub.devices.read(&mut token);
+        let (texture_guard, _) = hub.textures.read(&mut token);
+        let texture = texture_guard
+            .get(texture_id)
+            .map_err(|_| TransferError::InvalidTexture(texture_id))?;
+        let raw = texture
+            .raw
+            .as_ref()
+            .ok_or(TransferError::InvalidTexture(texture_id))?;
+        let device = &device_guard[texture.device_id.value];
+        Ok(unsafe { device.raw.texture_tiling(raw) })
+    }
+
+    /// Copies every mip level of `source` into `destination`, which the
+    /// backend lays out in its optimal tiling.
+    ///
+    /// Both textures need the same format, size, mip level count, sample
+    /// count and dimension. A destination reported as linear by
+    /// `texture_tiling` is rejected. On backends reporting an unknown tiling,
+    /// this is a plain copy of the whole texture.
+    pub fn command_encoder_retile_texture<A: HalApi>(
+        &self,
+        command_encoder_id: CommandEncoderId,
+        source: TextureId,
+        destination: TextureId,
+    ) -> Result<(), CopyError> {
+        profiling::scope!("retile_texture", "CommandEncoder");
+
+        let desc = {
+            let hub = A::hub(self);
+            let mut token = Token::root();
+            let (device_guard, mut token) = hub.devices.read(&mut token);
+            let (texture_guard, _) = hub.textures.read(&mut token);
+            let src_texture = texture_guard
+                .get(source)
+                .map_err(|_| TransferError::InvalidTexture(source))?;
+            let dst_texture = texture_guard
+                .get(destination)
+                .map_err(|_| TransferError::InvalidTexture(destination))?;
+            if !retile_descs_match(&src_texture.desc, &dst_texture.desc) {
+                return Err(TransferError::RetileMismatch {
+                    src: source,
+                    dst: destination,
+                }
+                .into());
+            }
+            let dst_raw = dst_texture
+                .raw
+                .as_ref()
+                .ok_or(TransferError::InvalidTexture(destination))?;
+            let device = &device_guard[dst_texture.device_id.value];
+            if unsafe { device.raw.texture_tiling(dst_raw) } == TextureTiling::Linear {
+                return Err(TransferError::LinearRetileDestination(destination).into());
+            }
+            src_texture.desc.clone()
+        };
+
+        for mip_level in 0..desc.mip_level_count {
+            let copy_size = desc.mip_level_size(mip_level).unwrap();
+            self.command_encoder_copy_texture_to_texture::<A>(
+                command_encoder_id,
+                &ImageCopyTexture {
+                    texture: source,
+                    mip_level,
+                    origin: wgt::Origin3d::ZERO,
+                    aspect: wgt::TextureAspect::All,
+                },
+                &ImageCopyTexture {
+                    texture: destination,
+                    mip_level,
+                    origin: wgt::Origin3d::ZERO,
+                    aspect: wgt::TextureAspect::All,
+                },
+                &copy_size,
+            )?;
         }
         Ok(())
     }
 }
+
+/// Returns true if a texture described by `src` can be re-tiled into one
+/// described by `dst`. Labels and usages are allowed to differ.
+fn retile_descs_match<L>(src: &wgt::TextureDescriptor<L>, dst: &wgt::TextureDescriptor<L>) -> bool {
+    src.format == dst.format
+        && src.size == dst.size
+        && src.mip_level_count == dst.mip_level_count
+        && src.sample_count == dst.sample_count
+        && src.dimension == dst.dimension
+}
+
+#[cfg(test)]
+fn test_texture_desc(
+    dimension: wgt::TextureDimension,
+    format: wgt::TextureFormat,
+    size: Extent3d,
+    mip_level_count: u32,
+) -> wgt::TextureDescriptor<()> {
+    wgt::TextureDescriptor {
+        label: (),
+        size,
+        mip_level_count,
+        sample_count: 1,
+        dimension,
+        format,
+        usage: TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+    }
+}
+
+#[cfg(test)]
+fn test_copy_texture(mip_level: u32, origin: wgt::Origin3d) -> ImageCopyTexture {
+    use crate::id::TypedId as _;
+    ImageCopyTexture {
+        texture: TextureId::zip(0, 1, wgt::Backend::Empty),
+        mip_level,
+        origin,
+        aspect: wgt::TextureAspect::All,
+    }
+}
+
+#[test]
+fn test_copy_range_last_mip_of_array_texture() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 16,
+            height: 16,
+            depth_or_array_layers: 6,
+        },
+        5,
+    );
+    let size = Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 6,
+    };
+    let copy = test_copy_texture(4, wgt::Origin3d::ZERO);
+    let (extent, layers) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size).unwrap();
+    assert_eq!(layers, 6);
+    assert_eq!(extent.depth, 1);
+
+    let copy = test_copy_texture(4, wgt::Origin3d { x: 0, y: 0, z: 4 });
+    let size = Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 3,
+    };
+    match validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size) {
+        Err(TransferError::TextureOverrun {
+            end_offset: 7,
+            texture_size: 6,
+            dimension: TextureErrorDimension::Z,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_copy_range_compressed_mip_clamps_from_origin() {
+    // Mip 3 is 5x5 texels, padded to 2x2 blocks of 4x4 texels.
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Bc1RgbaUnorm,
+        Extent3d {
+            width: 40,
+            height: 40,
+            depth_or_array_layers: 1,
+        },
+        4,
+    );
+    let size = |width, height| Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let copy = test_copy_texture(3, wgt::Origin3d::ZERO);
+    let (extent, _) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size(8, 8)).unwrap();
+    assert_eq!((extent.width, extent.height), (5, 5));
+
+    // The last block only holds a single column and row of texels.
+    let copy = test_copy_texture(3, wgt::Origin3d { x: 4, y: 4, z: 0 });
+    let (extent, _) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size(4, 4)).unwrap();
+    assert_eq!((extent.width, extent.height), (1, 1));
+
+    let copy = test_copy_texture(3, wgt::Origin3d { x: 4, y: 0, z: 0 });
+    let (extent, _) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size(4, 8)).unwrap();
+    assert_eq!((extent.width, extent.height), (1, 5));
+}
+
+#[test]
+fn test_copy_full_physical_extent_of_compressed_mip() {
+    // Mip 2 is 2x2 texels, stored in a single 4x4 block of 8 bytes.
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Bc1RgbaUnorm,
+        Extent3d {
+            width: 8,
+            height: 8,
+            depth_or_array_layers: 1,
+        },
+        3,
+    );
+    let physical = Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers: 1,
+    };
+    let copy = test_copy_texture(2, wgt::Origin3d::ZERO);
+
+    let (extent, layers) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &physical).unwrap();
+    assert_eq!((extent.width, extent.height, extent.depth), (2, 2, 1));
+    assert_eq!(layers, 1);
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: None,
+        rows_per_image: None,
+    };
+    let (required_bytes, _) = validate_linear_texture_data(
+        &layout,
+        desc.format,
+        8,
+        CopySide::Source,
+        8,
+        &physical,
+        false,
+    )
+    .unwrap();
+    assert_eq!(required_bytes, 8);
+
+    // The virtual size isn't a whole number of blocks.
+    let virtual_size = Extent3d {
+        width: 2,
+        height: 2,
+        depth_or_array_layers: 1,
+    };
+    match validate_texture_copy_range(&copy, &desc, CopySide::Destination, &virtual_size) {
+        Err(TransferError::UnalignedCopyWidth) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_copy_range_compressed_array_keeps_layers() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Bc1RgbaUnorm,
+        Extent3d {
+            width: 16,
+            height: 16,
+            depth_or_array_layers: 3,
+        },
+        5,
+    );
+    // The last mip is 1x1 virtually, but a full 4x4 block physically.
+    let size = Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers: 3,
+    };
+    let copy = test_copy_texture(4, wgt::Origin3d::ZERO);
+    let (_, layers) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size).unwrap();
+    assert_eq!(layers, 3);
+}
+
+#[test]
+fn test_aspect_block_size() {
+    use wgt::TextureFormat as Tf;
+    let color = hal::FormatAspects::COLOR;
+    let depth = hal::FormatAspects::DEPTH;
+    let stencil = hal::FormatAspects::STENCIL;
+    assert_eq!(aspect_block_size(Tf::Rgba8Unorm, color), 4);
+    assert_eq!(aspect_block_size(Tf::Bc1RgbaUnorm, color), 8);
+    assert_eq!(aspect_block_size(Tf::Depth32Float, depth), 4);
+    assert_eq!(
+        aspect_block_size(Tf::Depth24PlusStencil8, depth | stencil),
+        4
+    );
+    assert_eq!(aspect_block_size(Tf::Depth24PlusStencil8, stencil), 1);
+}
+
+#[test]
+fn test_stencil_readback_size() {
+    let format = wgt::TextureFormat::Depth24PlusStencil8;
+    let block_size = aspect_block_size(format, hal::FormatAspects::STENCIL);
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(wgt::COPY_BYTES_PER_ROW_ALIGNMENT),
+        rows_per_image: NonZeroU32::new(4),
+    };
+    let size = Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers: 1,
+    };
+    let (required_bytes_in_copy, _) = validate_linear_texture_data(
+        &layout,
+        format,
+        BufferAddress::MAX,
+        CopySide::Destination,
+        block_size as BufferAddress,
+        &size,
+        true,
+    )
+    .unwrap();
+    // three padded rows, and the last row holding 4 single-byte stencil texels
+    assert_eq!(
+        required_bytes_in_copy,
+        3 * wgt::COPY_BYTES_PER_ROW_ALIGNMENT as BufferAddress + 4
+    );
+}
+
+#[test]
+fn test_copy_aspect_all_single_aspect_formats() {
+    use wgt::{TextureAspect as Ta, TextureFormat as Tf};
+    let color_formats = [
+        Tf::R8Unorm,
+        Tf::Rg16Float,
+        Tf::Rgba8UnormSrgb,
+        Tf::Bgra8Unorm,
+        Tf::Rgba32Float,
+        Tf::Bc1RgbaUnorm,
+        Tf::Bc7RgbaUnormSrgb,
+    ];
+    for &format in color_formats.iter() {
+        assert_eq!(
+            texture_copy_aspect(format, Ta::All).unwrap(),
+            hal::FormatAspects::COLOR
+        );
+        for &aspect in [Ta::DepthOnly, Ta::StencilOnly].iter() {
+            assert!(matches!(
+                texture_copy_aspect(format, aspect),
+                Err(TransferError::InvalidTextureAspect { .. })
+            ));
+        }
+    }
+    // There are no stencil-only formats yet, so depth is the only single
+    // aspect of a depth/stencil format.
+    for &format in [Tf::Depth32Float, Tf::Depth24Plus].iter() {
+        assert_eq!(
+            texture_copy_aspect(format, Ta::All).unwrap(),
+            hal::FormatAspects::DEPTH
+        );
+        assert_eq!(
+            texture_copy_aspect(format, Ta::DepthOnly).unwrap(),
+            hal::FormatAspects::DEPTH
+        );
+        assert!(texture_copy_aspect(format, Ta::StencilOnly).is_err());
+    }
+    assert_eq!(
+        texture_copy_aspect(Tf::Depth24PlusStencil8, Ta::All).unwrap(),
+        hal::FormatAspects::DEPTH | hal::FormatAspects::STENCIL
+    );
+    assert_eq!(
+        texture_copy_aspect(Tf::Depth24PlusStencil8, Ta::StencilOnly).unwrap(),
+        hal::FormatAspects::STENCIL
+    );
+}
+
+#[test]
+fn test_undersized_rows_per_image() {
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(wgt::COPY_BYTES_PER_ROW_ALIGNMENT),
+        rows_per_image: NonZeroU32::new(2),
+    };
+    let validate = |depth_or_array_layers| {
+        validate_linear_texture_data(
+            &layout,
+            wgt::TextureFormat::Rgba8Unorm,
+            BufferAddress::MAX,
+            CopySide::Source,
+            4,
+            &Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers,
+            },
+            true,
+        )
+    };
+
+    match validate(1) {
+        Err(TransferError::InvalidRowsPerImage) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match validate(3) {
+        Err(TransferError::InvalidRowsPerImageForLayers {
+            rows_per_image: 2,
+            copy_height: 4,
+            layers: 3,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_check_image_data_layout() {
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(wgt::COPY_BYTES_PER_ROW_ALIGNMENT),
+        rows_per_image: NonZeroU32::new(4),
+    };
+    let extent = Extent3d {
+        width: 16,
+        height: 4,
+        depth_or_array_layers: 2,
+    };
+    let check = |format, buffer_size, aspect| {
+        check_image_data_layout(&layout, format, buffer_size, &extent, aspect)
+    };
+
+    assert_eq!(
+        check(
+            wgt::TextureFormat::Rgba8Unorm,
+            BufferAddress::MAX,
+            wgt::TextureAspect::All
+        )
+        .unwrap(),
+        LayoutInfo {
+            required_bytes: 256 * 7 + 64,
+            bytes_per_image: 256 * 4,
+        }
+    );
+    // The stencil aspect of a depth-stencil format has 1 byte texels.
+    assert_eq!(
+        check(
+            wgt::TextureFormat::Depth24PlusStencil8,
+            BufferAddress::MAX,
+            wgt::TextureAspect::StencilOnly
+        )
+        .unwrap()
+        .required_bytes,
+        256 * 7 + 16
+    );
+    match check(
+        wgt::TextureFormat::Rgba8Unorm,
+        256 * 7,
+        wgt::TextureAspect::All,
+    ) {
+        Err(TransferError::BufferOverrunInLastRow { .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match check(
+        wgt::TextureFormat::Rgba8Unorm,
+        BufferAddress::MAX,
+        wgt::TextureAspect::DepthOnly,
+    ) {
+        Err(TransferError::InvalidTextureAspect { .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_compressed_copy_less_than_a_block_high() {
+    // BC1 has 4x4 blocks of 8 bytes, a copy 2 texels high covers no complete row.
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(wgt::COPY_BYTES_PER_ROW_ALIGNMENT),
+        rows_per_image: None,
+    };
+    let result = validate_linear_texture_data(
+        &layout,
+        wgt::TextureFormat::Bc1RgbaUnorm,
+        BufferAddress::MAX,
+        CopySide::Source,
+        8,
+        &Extent3d {
+            width: 4,
+            height: 2,
+            depth_or_array_layers: 1,
+        },
+        true,
+    );
+    match result {
+        Err(TransferError::UnalignedCopyHeight) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_layout_narrower_than_copy() {
+    let copy_size = Extent3d {
+        width: 16,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let layout = |bytes_per_row| wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(bytes_per_row),
+        rows_per_image: None,
+    };
+    let validate = |layout: &wgt::ImageDataLayout| {
+        validate_linear_texture_data(
+            layout,
+            wgt::TextureFormat::Rgba8Unorm,
+            BufferAddress::MAX,
+            CopySide::Destination,
+            4,
+            &copy_size,
+            false,
+        )?;
+        validate_linear_layout_covers_copy(
+            layout,
+            wgt::TextureFormat::Rgba8Unorm,
+            CopySide::Destination,
+            4,
+            &copy_size,
+        )
+    };
+
+    // A single row passes the buffer range checks whatever its pitch, but the
+    // layout only describes 8 of the 16 texels of the source region.
+    match validate(&layout(32)) {
+        Err(TransferError::LayoutNarrowerThanCopy {
+            bytes_per_row: 32,
+            bytes_in_row: 64,
+            width: 16,
+            side: CopySide::Destination,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(validate(&layout(64)).is_ok());
+    assert!(validate(&layout(256)).is_ok());
+    assert!(validate(&layout(0)).is_ok());
+}
+
+#[test]
+fn test_copy_layers_out_of_bounds() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 4,
+        },
+        1,
+    );
+    let copy_size = |depth_or_array_layers| Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers,
+    };
+    let origin = |z| wgt::Origin3d { x: 0, y: 0, z };
+
+    let (layers, origin_z) = texture_copy_layers(
+        &desc,
+        &test_copy_texture(0, origin(1)),
+        &copy_size(3),
+        CopySide::Destination,
+    )
+    .unwrap();
+    assert_eq!((layers, origin_z), (1..4, 0));
+
+    match texture_copy_layers(
+        &desc,
+        &test_copy_texture(0, origin(2)),
+        &copy_size(3),
+        CopySide::Destination,
+    ) {
+        Err(TransferError::InvalidTextureArrayLayers {
+            start: 2,
+            end: 5,
+            total: 4,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match texture_copy_layers(
+        &desc,
+        &test_copy_texture(0, origin(4)),
+        &copy_size(1),
+        CopySide::Destination,
+    ) {
+        Err(TransferError::InvalidTextureArrayLayers {
+            start: 4,
+            end: 5,
+            total: 4,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    // the end of the layers overflows
+    match texture_copy_layers(
+        &desc,
+        &test_copy_texture(0, origin(u32::MAX)),
+        &copy_size(2),
+        CopySide::Source,
+    ) {
+        Err(TransferError::TextureOverrun {
+            start_offset: u32::MAX,
+            texture_size: 4,
+            dimension: TextureErrorDimension::Z,
+            side: CopySide::Source,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // the Z origin of 3D textures is validated with the copy range instead
+    let desc_3d = test_texture_desc(
+        wgt::TextureDimension::D3,
+        wgt::TextureFormat::Rgba8Unorm,
+        copy_size(4),
+        1,
+    );
+    let (layers, origin_z) = texture_copy_layers(
+        &desc_3d,
+        &test_copy_texture(0, origin(2)),
+        &copy_size(2),
+        CopySide::Destination,
+    )
+    .unwrap();
+    assert_eq!((layers, origin_z), (0..1, 2));
+}
+
+#[test]
+fn test_copy_between_mismatched_mip_levels() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 32,
+            height: 16,
+            depth_or_array_layers: 1,
+        },
+        5,
+    );
+    let size = |width, height| Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    // Mip 1 is 16x8, mip 2 is 8x4.
+    let (extent, layers) = validate_texture_to_texture_copy_range(
+        &test_copy_texture(1, wgt::Origin3d::ZERO),
+        &desc,
+        &test_copy_texture(2, wgt::Origin3d::ZERO),
+        &desc,
+        &size(8, 4),
+    )
+    .unwrap();
+    assert_eq!((extent.width, extent.height, extent.depth), (8, 4, 1));
+    assert_eq!(layers, 1);
+
+    // Fits into the source, but not the destination.
+    match validate_texture_to_texture_copy_range(
+        &test_copy_texture(1, wgt::Origin3d::ZERO),
+        &desc,
+        &test_copy_texture(2, wgt::Origin3d::ZERO),
+        &desc,
+        &size(16, 4),
+    ) {
+        Err(TransferError::TextureOverrun {
+            end_offset: 16,
+            texture_size: 8,
+            dimension: TextureErrorDimension::X,
+            side: CopySide::Destination,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Fits into the destination, but not the source.
+    match validate_texture_to_texture_copy_range(
+        &test_copy_texture(3, wgt::Origin3d::ZERO),
+        &desc,
+        &test_copy_texture(0, wgt::Origin3d::ZERO),
+        &desc,
+        &size(4, 4),
+    ) {
+        Err(TransferError::TextureOverrun {
+            end_offset: 4,
+            texture_size: 2,
+            dimension: TextureErrorDimension::Y,
+            side: CopySide::Source,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_single_aspect_texture_to_texture_copy() {
+    use wgt::{TextureAspect as Ta, TextureFormat as Tf};
+    assert_eq!(
+        texture_to_texture_copy_aspect(
+            Tf::Depth24PlusStencil8,
+            Ta::DepthOnly,
+            Tf::Depth24PlusStencil8,
+            Ta::DepthOnly
+        )
+        .unwrap(),
+        hal::FormatAspects::DEPTH
+    );
+    assert_eq!(
+        texture_to_texture_copy_aspect(
+            Tf::Depth24PlusStencil8,
+            Ta::StencilOnly,
+            Tf::Depth24PlusStencil8,
+            Ta::StencilOnly
+        )
+        .unwrap(),
+        hal::FormatAspects::STENCIL
+    );
+    // depth-only on one side selects the whole of a depth texture on the other
+    assert_eq!(
+        texture_to_texture_copy_aspect(
+            Tf::Depth32Float,
+            Ta::All,
+            Tf::Depth24PlusStencil8,
+            Ta::DepthOnly
+        )
+        .unwrap(),
+        hal::FormatAspects::DEPTH
+    );
+    match texture_to_texture_copy_aspect(
+        Tf::Depth24PlusStencil8,
+        Ta::All,
+        Tf::Depth24PlusStencil8,
+        Ta::StencilOnly,
+    ) {
+        Err(TransferError::MismatchedAspects) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_copy_range_3d_depth_slices() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D3,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 8,
+        },
+        2,
+    );
+    let size = Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers: 4,
+    };
+    let origin = wgt::Origin3d { x: 0, y: 0, z: 2 };
+
+    let copy = test_copy_texture(0, origin);
+    let (extent, layers) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size).unwrap();
+    assert_eq!((extent.width, extent.height, extent.depth), (4, 4, 4));
+    assert_eq!(layers, 1);
+    let (_, origin_z) = texture_copy_layers(&desc, &copy, &size, CopySide::Destination).unwrap();
+    assert_eq!(origin_z, 2);
+
+    // slices 6..10 are past the end of the texture
+    let copy = test_copy_texture(0, wgt::Origin3d { x: 0, y: 0, z: 6 });
+    match validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size) {
+        Err(TransferError::TextureOverrun {
+            start_offset: 6,
+            end_offset: 10,
+            texture_size: 8,
+            dimension: TextureErrorDimension::Z,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // the second mip level is only 4 slices deep
+    let copy = test_copy_texture(1, origin);
+    let size = Extent3d {
+        width: 2,
+        height: 2,
+        depth_or_array_layers: 4,
+    };
+    match validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size) {
+        Err(TransferError::TextureOverrun {
+            end_offset: 6,
+            texture_size: 4,
+            dimension: TextureErrorDimension::Z,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_texture_subresource_size() {
+    let size = Extent3d {
+        width: 10,
+        height: 6,
+        depth_or_array_layers: 4,
+    };
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Rgba8Unorm,
+        size,
+        3,
+    );
+    let color = hal::FormatAspects::COLOR;
+    assert_eq!(
+        texture_subresource_size(&desc, 0, 0..4, color).unwrap(),
+        10 * 6 * 4 * 4
+    );
+    assert_eq!(
+        texture_subresource_size(&desc, 2, 1..3, color).unwrap(),
+        2 * 4 * 2
+    );
+    match texture_subresource_size(&desc, 3, 0..1, color) {
+        Err(TransferError::InvalidTextureMipLevel { level: 3, total: 3 }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match texture_subresource_size(&desc, 0, 2..5, color) {
+        Err(TransferError::InvalidTextureArrayLayers {
+            start: 2,
+            end: 5,
+            total: 4,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // mips of compressed textures are padded to whole blocks
+    let desc_bc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Bc1RgbaUnorm,
+        size,
+        3,
+    );
+    assert_eq!(
+        texture_subresource_size(&desc_bc, 2, 0..1, color).unwrap(),
+        8
+    );
+
+    // every depth slice of a 3D mip is counted
+    let desc_3d = test_texture_desc(
+        wgt::TextureDimension::D3,
+        wgt::TextureFormat::R8Unorm,
+        size,
+        2,
+    );
+    assert_eq!(
+        texture_subresource_size(&desc_3d, 1, 0..1, color).unwrap(),
+        5 * 3 * 2
+    );
+
+    // stencil is a single byte per texel
+    let desc_ds = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Depth24PlusStencil8,
+        size,
+        1,
+    );
+    assert_eq!(
+        texture_subresource_size(&desc_ds, 0, 0..1, hal::FormatAspects::STENCIL).unwrap(),
+        10 * 6
+    );
+}
+
+#[test]
+fn test_readback_buffer_short_of_last_row() {
+    let copy_size = Extent3d {
+        width: 10,
+        height: 4,
+        depth_or_array_layers: 1,
+    };
+    let layout = wgt::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: NonZeroU32::new(256),
+        rows_per_image: None,
+    };
+    let validate = |buffer_size| {
+        validate_linear_texture_data(
+            &layout,
+            wgt::TextureFormat::Rgba8Unorm,
+            buffer_size,
+            CopySide::Destination,
+            4,
+            &copy_size,
+            true,
+        )
+    };
+
+    // the last row only needs the 40 bytes of its texels
+    let (required, _) = validate(256 * 3 + 40).unwrap();
+    assert_eq!(required, 256 * 3 + 40);
+
+    // a buffer holding the padded rows only is missing the last one
+    match validate(256 * 3) {
+        Err(TransferError::BufferOverrunInLastRow {
+            start_offset: 0,
+            end_offset: 808,
+            buffer_size: 768,
+            bytes_per_row: 256,
+            bytes_in_last_row: 40,
+            side: CopySide::Destination,
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // missing more than the last row is a plain overrun
+    match validate(256 * 2) {
+        Err(TransferError::BufferOverrun {
+            end_offset: 808,
+            buffer_size: 512,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_buffer_range_near_address_space_end() {
+    let offset = BufferAddress::MAX - 3;
+    match validate_buffer_range(offset, 8, 16, CopySide::Destination) {
+        Err(TransferError::BufferAddressOverflow {
+            start_offset,
+            size: 8,
+            side: CopySide::Destination,
+        }) if start_offset == offset => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match validate_buffer_range(offset, 4, 16, CopySide::Source) {
+        Err(TransferError::BufferOverrun {
+            end_offset: BufferAddress::MAX,
+            buffer_size: 16,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(
+        validate_buffer_range(8, 8, 16, CopySide::Source).unwrap(),
+        16
+    );
+}
+
+#[test]
+fn test_mapped_source_buffer_is_rejected() {
+    use crate::device::HostMap;
+    use crate::id::TypedId as _;
+
+    let buffer_id = BufferId::zip(0, 1, wgt::Backend::Empty);
+    let idle = BufferMapState::<hal::api::Empty>::Idle;
+    assert!(validate_source_buffer_unmapped(&idle, buffer_id).is_ok());
+
+    let mapped = BufferMapState::<hal::api::Empty>::Active {
+        ptr: std::ptr::NonNull::dangling(),
+        range: 0..4,
+        host: HostMap::Write,
+    };
+    assert!(matches!(
+        validate_source_buffer_unmapped(&mapped, buffer_id),
+        Err(TransferError::MappedSourceBuffer(id)) if id == buffer_id
+    ));
+}
+
+#[test]
+fn test_texture_copy_formats() {
+    use wgt::TextureFormat as Tf;
+    assert!(validate_texture_copy_formats(Tf::Rgba8Unorm, Tf::Rgba8Unorm).is_ok());
+    assert!(validate_texture_copy_formats(Tf::Rgba8UnormSrgb, Tf::Rgba8Unorm).is_ok());
+    assert!(validate_texture_copy_formats(Tf::Bc1RgbaUnorm, Tf::Bc1RgbaUnormSrgb).is_ok());
+    // Same size of texels, but the channels would need to be swizzled or reinterpreted.
+    for &(src, dst) in [
+        (Tf::Rgba8Unorm, Tf::Bgra8Unorm),
+        (Tf::Rgba8UnormSrgb, Tf::Bgra8UnormSrgb),
+        (Tf::R32Float, Tf::Rgba8Unorm),
+        (Tf::Bc1RgbaUnorm, Tf::Bc4RUnorm),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            validate_texture_copy_formats(src, dst),
+            Err(TransferError::MismatchedTextureFormats { .. })
+        ));
+        assert!(!formats_copy_compatible(src, dst));
+        assert!(!formats_copy_compatible(dst, src));
+    }
+    // Same-block-size aliases within a family aren't supported either.
+    for &(src, dst) in [
+        (Tf::Rgba8Unorm, Tf::Rgba8Snorm),
+        (Tf::Rgba8Unorm, Tf::Rgba8Uint),
+        (Tf::Rgba8UnormSrgb, Tf::Rgba8Sint),
+        (Tf::R32Float, Tf::R32Uint),
+        (Tf::Rg16Sint, Tf::Rg16Uint),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            validate_texture_copy_formats(src, dst),
+            Err(TransferError::MismatchedTextureFormats { .. })
+        ));
+        assert!(!formats_copy_compatible(src, dst));
+    }
+    // The query is symmetric, like the check.
+    assert!(formats_copy_compatible(Tf::Bgra8Unorm, Tf::Bgra8UnormSrgb));
+    assert!(formats_copy_compatible(Tf::Bgra8UnormSrgb, Tf::Bgra8Unorm));
+    assert!(formats_copy_compatible(Tf::Depth32Float, Tf::Depth32Float));
+}
+
+#[test]
+fn test_estimate_transfer_ns() {
+    assert_eq!(estimate_transfer_ns(0), 0);
+    assert_eq!(estimate_transfer_ns(1), 1);
+    assert_eq!(estimate_transfer_ns(17), 2);
+    assert_eq!(estimate_transfer_ns(16 << 20), 1 << 20);
+    let stats = CopyStats::new(256);
+    assert_eq!(stats.bytes, 256);
+    assert_eq!(stats.estimated_ns, 16);
+}
+
+#[test]
+fn test_texture_readback_layouts() {
+    // Two levels of two layers, 16x4 then 8x2 RGBA8 texels.
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D2,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 16,
+            height: 4,
+            depth_or_array_layers: 2,
+        },
+        2,
+    );
+    let (layouts, end) = texture_readback_layouts(&desc, wgt::TextureAspect::All, 256).unwrap();
+    let summary = layouts
+        .iter()
+        .map(|l| {
+            (
+                l.mip_level,
+                l.array_layer,
+                l.offset,
+                l.bytes_per_row,
+                l.rows_per_image,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (0, 0, 256, 256, 4),
+            (0, 1, 256 + 1024, 256, 4),
+            (1, 0, 256 + 2048, 256, 2),
+            (1, 1, 256 + 2048 + 512, 256, 2),
+        ]
+    );
+    assert_eq!(layouts[2].size.width, 8);
+    assert_eq!(layouts[2].size.depth_or_array_layers, 1);
+    // The last row of the last layer isn't padded.
+    assert_eq!(end, 256 + 2048 + 512 + 256 + 8 * 4);
+
+    // A 3D texture has a single subresource per level, holding all its slices.
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D3,
+        wgt::TextureFormat::R8Unorm,
+        Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 4,
+        },
+        2,
+    );
+    let (layouts, _) = texture_readback_layouts(&desc, wgt::TextureAspect::All, 0).unwrap();
+    assert_eq!(layouts.len(), 2);
+    assert_eq!(layouts[1].offset, 256 * 4 * 4);
+    assert_eq!(layouts[1].size.depth_or_array_layers, 2);
+}
+
+#[test]
+fn test_copy_debug_marker() {
+    assert_eq!(
+        copy_debug_marker(false, || unreachable!("formatted a disabled marker")),
+        None
+    );
+    assert_eq!(
+        copy_debug_marker(true, || format!("copy {} bytes", 256)).as_deref(),
+        Some("copy 256 bytes")
+    );
+}
+
+#[test]
+fn test_copy_range_of_1d_texture() {
+    let desc = test_texture_desc(
+        wgt::TextureDimension::D1,
+        wgt::TextureFormat::Rgba8Unorm,
+        Extent3d {
+            width: 64,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        1,
+    );
+    let copy = test_copy_texture(0, wgt::Origin3d::ZERO);
+    let size = |height, depth_or_array_layers| Extent3d {
+        width: 64,
+        height,
+        depth_or_array_layers,
+    };
+
+    let (extent, layers) =
+        validate_texture_copy_range(&copy, &desc, CopySide::Destination, &size(1, 1)).unwrap();
+    assert_eq!((extent.width, extent.height, extent.depth), (64, 1, 1));
+    assert_eq!(layers, 1);
+    for &(height, depth) in [(2, 1), (1, 2), (2, 2)].iter() {
+        for side in [CopySide::Source, CopySide::Destination].iter() {
+            assert!(matches!(
+                validate_texture_copy_range(&copy, &desc, side.clone(), &size(height, depth)),
+                Err(TransferError::InvalidCopySize)
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_ranges_overlap() {
+    assert!(ranges_overlap(&(0..8), &(4..12)));
+    assert!(ranges_overlap(&(4..12), &(0..8)));
+    assert!(ranges_overlap(&(0..16), &(4..8)));
+    // Adjacent ranges don't share anything.
+    assert!(!ranges_overlap(&(0..8), &(8..16)));
+    assert!(!ranges_overlap(&(8..16), &(0..8)));
+}
+
+#[test]
+fn test_validate_copy_subresources() {
+    use crate::id::TypedId as _;
+
+    let texture = TextureId::zip(0, 1, wgt::Backend::Empty);
+    let other = TextureId::zip(1, 1, wgt::Backend::Empty);
+    let selector = |levels, layers| TextureSelector { levels, layers };
+
+    match validate_copy_subresources(
+        texture,
+        &selector(0..1, 0..2),
+        texture,
+        &selector(0..1, 1..3),
+    ) {
+        Err(TransferError::TextureUsageConflict {
+            texture: id,
+            current: hal::TextureUses::COPY_SRC,
+            requested: hal::TextureUses::COPY_DST,
+        }) => assert_eq!(id, texture),
+        result => panic!("unexpected result {:?}", result),
+    }
+    // Other mips and layers of the same texture are separate subresources.
+    assert!(validate_copy_subresources(
+        texture,
+        &selector(0..1, 0..2),
+        texture,
+        &selector(1..2, 0..2)
+    )
+    .is_ok());
+    assert!(validate_copy_subresources(
+        texture,
+        &selector(0..1, 0..2),
+        texture,
+        &selector(0..1, 2..4)
+    )
+    .is_ok());
+    assert!(validate_copy_subresources(
+        texture,
+        &selector(0..1, 0..1),
+        other,
+        &selector(0..1, 0..1)
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_retile_descs_match() {
+    use wgt::{TextureDimension as Td, TextureFormat as Tf};
+
+    let size = Extent3d {
+        width: 64,
+        height: 64,
+        depth_or_array_layers: 1,
+    };
+    let src = test_texture_desc(Td::D2, Tf::Rgba8Unorm, size, 7);
+
+    // Only the usages differ.
+    let mut dst = src.clone();
+    dst.usage = TextureUsages::COPY_DST | TextureUsages::SAMPLED;
+    assert!(retile_descs_match(&src, &dst));
+
+    assert!(!retile_descs_match(
+        &src,
+        &test_texture_desc(Td::D2, Tf::Bgra8Unorm, size, 7)
+    ));
+    assert!(!retile_descs_match(
+        &src,
+        &test_texture_desc(Td::D2, Tf::Rgba8Unorm, size, 6)
+    ));
+    assert!(!retile_descs_match(
+        &src,
+        &test_texture_desc(Td::D3, Tf::Rgba8Unorm, size, 7)
+    ));
+    let mut dst = src.clone();
+    dst.sample_count = 4;
+    assert!(!retile_descs_match(&src, &dst));
+}
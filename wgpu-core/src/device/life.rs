@@ -2,7 +2,7 @@
 use crate::device::trace;
 use crate::{
     device::{
-        queue::{EncoderInFlight, TempResource},
+        queue::{EncoderInFlight, TempResource, WorkDoneSignal},
         DeviceError,
     },
     hub::{GlobalIdentityHandlerFactory, HalApi, Hub, Token},
@@ -16,7 +16,11 @@ use hal::Device as _;
 use parking_lot::Mutex;
 use thiserror::Error;
 
-use std::{mem, sync::atomic::Ordering};
+use std::{
+    mem,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 
 /// A struct that keeps lists of resources that are no longer needed by the user.
 #[derive(Debug, Default)]
@@ -126,38 +130,50 @@ impl<A: hal::Api> NonReferencedResources<A> {
         assert!(other.pipeline_layouts.is_empty());
     }
 
-    unsafe fn clean(&mut self, device: &A::Device) {
-        for raw in self.buffers.drain(..) {
-            device.destroy_buffer(raw);
-        }
-        for raw in self.textures.drain(..) {
-            device.destroy_texture(raw);
-        }
-        for (_, raw) in self.texture_views.drain(..) {
-            device.destroy_texture_view(raw);
-        }
-        for raw in self.samplers.drain(..) {
-            device.destroy_sampler(raw);
-        }
-        for raw in self.bind_groups.drain(..) {
-            device.destroy_bind_group(raw);
-        }
-        for raw in self.compute_pipes.drain(..) {
-            device.destroy_compute_pipeline(raw);
-        }
-        for raw in self.render_pipes.drain(..) {
-            device.destroy_render_pipeline(raw);
-        }
-        for raw in self.bind_group_layouts.drain(..) {
-            device.destroy_bind_group_layout(raw);
-        }
-        for raw in self.pipeline_layouts.drain(..) {
-            device.destroy_pipeline_layout(raw);
-        }
-        for raw in self.query_sets.drain(..) {
-            device.destroy_query_set(raw);
+    /// Destroy the resources, stopping once the `deadline` has passed.
+    ///
+    /// Returns `false` if some resources are left to destroy.
+    unsafe fn clean(&mut self, device: &A::Device, deadline: Option<Instant>) -> bool {
+        destroy_until(&mut self.buffers, deadline, |raw| {
+            device.destroy_buffer(raw)
+        }) && destroy_until(&mut self.textures, deadline, |raw| {
+            device.destroy_texture(raw)
+        }) && destroy_until(&mut self.texture_views, deadline, |(_, raw)| {
+            device.destroy_texture_view(raw)
+        }) && destroy_until(&mut self.samplers, deadline, |raw| {
+            device.destroy_sampler(raw)
+        }) && destroy_until(&mut self.bind_groups, deadline, |raw| {
+            device.destroy_bind_group(raw)
+        }) && destroy_until(&mut self.compute_pipes, deadline, |raw| {
+            device.destroy_compute_pipeline(raw)
+        }) && destroy_until(&mut self.render_pipes, deadline, |raw| {
+            device.destroy_render_pipeline(raw)
+        }) && destroy_until(&mut self.bind_group_layouts, deadline, |raw| {
+            device.destroy_bind_group_layout(raw)
+        }) && destroy_until(&mut self.pipeline_layouts, deadline, |raw| {
+            device.destroy_pipeline_layout(raw)
+        }) && destroy_until(&mut self.query_sets, deadline, |raw| {
+            device.destroy_query_set(raw)
+        })
+    }
+}
+
+/// Destroy the resources of `list`, stopping once the `deadline` has passed.
+///
+/// At least one resource is destroyed, so that the cleanup always makes
+/// progress. Returns `false` if some resources are left in the list.
+fn destroy_until<T>(
+    list: &mut Vec<T>,
+    deadline: Option<Instant>,
+    mut destroy: impl FnMut(T),
+) -> bool {
+    while let Some(raw) = list.pop() {
+        destroy(raw);
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return list.is_empty();
         }
     }
+    true
 }
 
 struct ActiveSubmission<A: hal::Api> {
@@ -165,6 +181,7 @@ struct ActiveSubmission<A: hal::Api> {
     last_resources: NonReferencedResources<A>,
     mapped: Vec<id::Valid<id::BufferId>>,
     encoders: Vec<EncoderInFlight<A>>,
+    work_done: Vec<Arc<WorkDoneSignal>>,
 }
 
 #[derive(Clone, Debug, Error)]
@@ -235,9 +252,21 @@ impl<A: hal::Api> LifetimeTracker<A> {
             last_resources,
             mapped: Vec::new(),
             encoders,
+            work_done: Vec::new(),
         });
     }
 
+    /// Complete the `signal` once the submission with the given index is done.
+    ///
+    /// Submissions that aren't active anymore are done already,
+    /// so their signal is completed right away.
+    pub fn add_work_done_signal(&mut self, index: SubmissionIndex, signal: Arc<WorkDoneSignal>) {
+        match self.active.iter_mut().find(|a| a.index == index) {
+            Some(submission) => submission.work_done.push(signal),
+            None => signal.complete(),
+        }
+    }
+
     pub fn post_submit(&mut self) {
         self.suspected_resources.buffers.extend(
             self.future_suspected_buffers
@@ -259,6 +288,7 @@ impl<A: hal::Api> LifetimeTracker<A> {
     pub fn triage_submissions(
         &mut self,
         last_done: SubmissionIndex,
+        device: &A::Device,
         command_allocator: &Mutex<super::CommandAllocator<A>>,
     ) {
         profiling::scope!("triage_submissions");
@@ -277,16 +307,24 @@ impl<A: hal::Api> LifetimeTracker<A> {
             self.ready_to_map.extend(a.mapped);
             for encoder in a.encoders {
                 let raw = unsafe { encoder.land() };
-                command_allocator.lock().release_encoder(raw);
+                command_allocator.lock().release_encoder(device, raw);
+            }
+            for signal in a.work_done {
+                signal.complete();
             }
         }
     }
 
     pub fn cleanup(&mut self, device: &A::Device) {
+        self.cleanup_until(device, None);
+    }
+
+    /// Like `cleanup`, but stops once the `deadline` has passed.
+    ///
+    /// Returns `false` if some resources are left for a later cleanup.
+    pub fn cleanup_until(&mut self, device: &A::Device, deadline: Option<Instant>) -> bool {
         profiling::scope!("cleanup");
-        unsafe {
-            self.free_resources.clean(device);
-        }
+        unsafe { self.free_resources.clean(device, deadline) }
     }
 
     pub fn schedule_resource_destruction(
@@ -690,3 +728,37 @@ impl<A: HalApi> LifetimeTracker<A> {
         pending_callbacks
     }
 }
+
+#[test]
+fn test_destroy_until() {
+    use std::time::Duration;
+
+    let mut destroyed = Vec::new();
+    let mut list = vec![1, 2, 3];
+    assert!(destroy_until(&mut list, None, |raw| destroyed.push(raw)));
+    assert!(list.is_empty());
+    assert_eq!(destroyed, [3, 2, 1]);
+
+    // Past the deadline, a single resource is destroyed per call.
+    let deadline = Instant::now();
+    let mut destroyed = Vec::new();
+    let mut list = vec![1, 2, 3];
+    assert!(!destroy_until(&mut list, Some(deadline), |raw| destroyed.push(raw)));
+    assert_eq!(destroyed, [3]);
+    assert!(!destroy_until(&mut list, Some(deadline), |raw| destroyed.push(raw)));
+    assert!(destroy_until(&mut list, Some(deadline), |raw| destroyed.push(raw)));
+    assert_eq!(destroyed, [3, 2, 1]);
+
+    // An empty list is done, whatever the deadline.
+    assert!(destroy_until(
+        &mut list,
+        Some(deadline),
+        |_: i32| unreachable!()
+    ));
+
+    // Far enough ahead, everything is destroyed.
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let mut list = vec![1, 2, 3];
+    assert!(destroy_until(&mut list, Some(deadline), |_| {}));
+    assert!(list.is_empty());
+}
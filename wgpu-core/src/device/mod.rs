@@ -18,7 +18,16 @@ use parking_lot::{Mutex, MutexGuard};
 use thiserror::Error;
 use wgt::{BufferAddress, TextureFormat, TextureViewDimension};
 
-use std::{borrow::Cow, iter, marker::PhantomData, mem, ops::Range, ptr, sync::atomic::Ordering};
+use std::{
+    borrow::Cow,
+    iter,
+    marker::PhantomData,
+    mem,
+    ops::Range,
+    ptr,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 mod life;
 pub mod queue;
@@ -27,6 +36,9 @@ pub mod trace;
 
 pub const SHADER_STAGE_COUNT: usize = 3;
 const CLEANUP_WAIT_MS: u32 = 5000;
+/// Maximum number of idle command encoders kept around for reuse, enough for
+/// about 20 command buffers per frame with 3 frames in flight.
+const MAX_FREE_COMMAND_ENCODERS: usize = 64;
 
 const IMPLICIT_FAILURE: &str = "failed implicit";
 const EP_FAILURE: &str = "EP is invalid";
@@ -179,6 +191,8 @@ fn fire_map_callbacks<I: IntoIterator<Item = BufferMapPendingCallback>>(callback
 
 struct CommandAllocator<A: hal::Api> {
     free_encoders: Vec<A::CommandEncoder>,
+    /// Number of encoders created so far.
+    created: u64,
 }
 
 impl<A: hal::Api> CommandAllocator<A> {
@@ -190,14 +204,25 @@ impl<A: hal::Api> CommandAllocator<A> {
         match self.free_encoders.pop() {
             Some(encoder) => Ok(encoder),
             None => unsafe {
+                log::trace!("Creating a new command encoder");
                 let hal_desc = hal::CommandEncoderDescriptor { label: None, queue };
-                device.create_command_encoder(&hal_desc)
+                let encoder = device.create_command_encoder(&hal_desc)?;
+                self.created += 1;
+                Ok(encoder)
             },
         }
     }
 
-    fn release_encoder(&mut self, encoder: A::CommandEncoder) {
-        self.free_encoders.push(encoder);
+    /// Return an encoder that has been reset, so that it can be reused.
+    fn release_encoder(&mut self, device: &A::Device, encoder: A::CommandEncoder) {
+        if self.free_encoders.len() < MAX_FREE_COMMAND_ENCODERS {
+            self.free_encoders.push(encoder);
+        } else {
+            log::trace!("Destroying a surplus command encoder");
+            unsafe {
+                device.destroy_command_encoder(encoder);
+            }
+        }
     }
 
     fn dispose(self, device: &A::Device) {
@@ -244,6 +269,22 @@ pub struct Device<A: hal::Api> {
     //TODO: move this behind another mutex. This would allow several methods to switch
     // to borrow Device immutably, such as `write_buffer`, `write_texture`, and `buffer_unmap`.
     pending_writes: queue::PendingWrites<A>,
+    write_texture_repack: queue::WriteTextureRepack,
+    unsubmitted_writes: queue::UnsubmittedWrites,
+    write_after_read: queue::WriteAfterRead,
+    /// Minimum size of the all-zero writes that are filled instead of staged.
+    zero_write_fill_threshold: Option<wgt::BufferAddress>,
+    /// Whether copies are recorded in debug groups, for GPU captures.
+    copy_debug_markers: bool,
+    staging_map_stats: queue::StagingMapStats,
+    #[cfg(feature = "staging-stats")]
+    last_staging: Option<queue::StagingInfo>,
+    copy_limits: queue::CopyLimits,
+    /// Submission that ran out of memory, kept until it's retried.
+    failed_submission: Option<queue::FailedSubmission<A>>,
+    /// How long to wait for the GPU, in milliseconds, before considering it stuck,
+    /// or `None` to wait for as long as it takes.
+    stuck_gpu_timeout_ms: Option<u32>,
     #[cfg(feature = "trace")]
     pub(crate) trace: Option<Mutex<trace::Trace>>,
 }
@@ -294,6 +335,7 @@ impl<A: HalApi> Device<A> {
 
         let mut com_alloc = CommandAllocator {
             free_encoders: Vec::new(),
+            created: 0,
         };
         let pending_encoder = com_alloc
             .acquire_encoder(&open.device, &open.queue)
@@ -317,6 +359,7 @@ impl<A: HalApi> Device<A> {
                     trace.add(trace::Action::Init {
                         desc: desc.clone(),
                         backend: A::VARIANT,
+                        version: trace::FILE_VERSION,
                     });
                     Some(Mutex::new(trace))
                 }
@@ -330,6 +373,17 @@ impl<A: HalApi> Device<A> {
             features: desc.features,
             downlevel,
             pending_writes,
+            write_texture_repack: queue::WriteTextureRepack::default(),
+            unsubmitted_writes: queue::UnsubmittedWrites::default(),
+            write_after_read: queue::WriteAfterRead::default(),
+            zero_write_fill_threshold: None,
+            copy_debug_markers: false,
+            staging_map_stats: queue::StagingMapStats::default(),
+            #[cfg(feature = "staging-stats")]
+            last_staging: None,
+            copy_limits: queue::CopyLimits::from_limits(&desc.limits),
+            failed_submission: None,
+            stuck_gpu_timeout_ms: Some(CLEANUP_WAIT_MS),
         })
     }
 
@@ -357,7 +411,24 @@ impl<A: HalApi> Device<A> {
         force_wait: bool,
         token: &mut Token<'token, Self>,
     ) -> Result<Vec<BufferMapPendingCallback>, WaitIdleError> {
+        self.maintain_bounded(hub, force_wait, None, token)
+            .map(|(callbacks, _)| callbacks)
+    }
+
+    /// Like `maintain`, but stops destroying resources after `cleanup_budget_ms`.
+    ///
+    /// The returned flag is `true` if the budget ran out, leaving some
+    /// resources to a later maintenance pass.
+    fn maintain_bounded<'this, 'token: 'this, G: GlobalIdentityHandlerFactory>(
+        &'this self,
+        hub: &Hub<A, G>,
+        force_wait: bool,
+        cleanup_budget_ms: Option<u32>,
+        token: &mut Token<'token, Self>,
+    ) -> Result<(Vec<BufferMapPendingCallback>, bool), WaitIdleError> {
         profiling::scope!("maintain", "Device");
+        let deadline = cleanup_budget_ms
+            .map(|budget_ms| Instant::now() + Duration::from_millis(budget_ms.into()));
         let mut life_tracker = self.lock_life(token);
 
         life_tracker
@@ -374,12 +445,8 @@ impl<A: HalApi> Device<A> {
         life_tracker.triage_mapped(hub, token);
 
         let last_done_index = if force_wait {
-            let current_index = self.active_submission_index;
-            unsafe {
-                self.raw
-                    .wait(&self.fence, current_index, CLEANUP_WAIT_MS)
-                    .map_err(DeviceError::from)?
-            };
+            let current_index = self.last_submitted_index();
+            self.wait_for_gpu(current_index)?;
             current_index
         } else {
             unsafe {
@@ -389,11 +456,41 @@ impl<A: HalApi> Device<A> {
             }
         };
 
-        life_tracker.triage_submissions(last_done_index, &self.command_allocator);
+        life_tracker.triage_submissions(last_done_index, &self.raw, &self.command_allocator);
         let callbacks = life_tracker.handle_mapping(hub, &self.raw, &self.trackers, token);
-        life_tracker.cleanup(&self.raw);
+        let cleaned_up = life_tracker.cleanup_until(&self.raw, deadline);
 
-        Ok(callbacks)
+        Ok((callbacks, !cleaned_up))
+    }
+
+    /// Wait for the submission `index` to be done, and return `StuckGpu`
+    /// if that takes longer than the stuck GPU timeout.
+    fn wait_for_gpu(&self, index: SubmissionIndex) -> Result<(), WaitIdleError> {
+        match self.stuck_gpu_timeout_ms {
+            Some(timeout_ms) => {
+                let done = unsafe {
+                    self.raw
+                        .wait(&self.fence, index, timeout_ms)
+                        .map_err(DeviceError::from)?
+                };
+                if !done {
+                    return Err(WaitIdleError::StuckGpu);
+                }
+            }
+            None => {
+                while !unsafe {
+                    self.raw
+                        .wait(&self.fence, index, CLEANUP_WAIT_MS)
+                        .map_err(DeviceError::from)?
+                } {
+                    log::info!(
+                        "Still waiting for submission {}, the GPU is never considered stuck",
+                        index
+                    );
+                }
+            }
+        }
+        Ok(())
     }
 
     fn untrack<'this, 'token: 'this, G: GlobalIdentityHandlerFactory>(
@@ -494,9 +591,15 @@ impl<A: HalApi> Device<A> {
         let mut memory_flags = hal::MemoryFlags::empty();
         memory_flags.set(hal::MemoryFlags::TRANSIENT, transient);
 
+        // Round the allocation up to the copy alignment, so that lazy
+        // initialization can always fill whole words, even at the end.
+        let actual_size = match desc.size % wgt::COPY_BUFFER_ALIGNMENT {
+            0 => desc.size,
+            unaligned => desc.size + wgt::COPY_BUFFER_ALIGNMENT - unaligned,
+        };
         let hal_desc = hal::BufferDescriptor {
             label: desc.label.borrow_option(),
-            size: desc.size,
+            size: actual_size,
             usage,
             memory_flags,
         };
@@ -511,6 +614,7 @@ impl<A: HalApi> Device<A> {
             usage: desc.usage,
             size: desc.size,
             initialization_status: MemoryInitTracker::new(desc.size),
+            init_policy: resource::BufferInitPolicy::default(),
             sync_mapped_writes: None,
             map_state: resource::BufferMapState::Idle,
             life_guard: LifeGuard::new(desc.label.borrow_or_default()),
@@ -720,6 +824,10 @@ impl<A: HalApi> Device<A> {
         if view_dim != wgt::TextureViewDimension::D3 {
             extent.depth_or_array_layers = view_layer_count;
         }
+        // Views always have the format of their texture. Anything writing or
+        // copying through a view can rely on this, instead of having to check
+        // that a reinterpreted format (such as sRGB of a linear texture) is
+        // copy-compatible with the texture.
         let format = desc.format.unwrap_or(texture.desc.format);
         if format != texture.desc.format {
             return Err(resource::CreateTextureViewError::FormatReinterpretation {
@@ -2355,8 +2463,11 @@ impl<A: HalApi> Device<A> {
                     .wait(&self.fence, submission_index, !0)
                     .map_err(DeviceError::from)?
             };
-            self.lock_life(token)
-                .triage_submissions(submission_index, &self.command_allocator);
+            self.lock_life(token).triage_submissions(
+                submission_index,
+                &self.raw,
+                &self.command_allocator,
+            );
         }
         Ok(())
     }
@@ -2419,7 +2530,8 @@ impl<A: hal::Api> Device<A> {
         }
     }
 
-    pub(crate) fn destroy_command_buffer(&self, cmd_buf: command::CommandBuffer<A>) {
+    pub(crate) fn destroy_command_buffer(&self, mut cmd_buf: command::CommandBuffer<A>) {
+        cmd_buf.discard_encoding();
         let mut baked = cmd_buf.into_baked();
         unsafe {
             baked.encoder.reset_all(baked.list.into_iter());
@@ -2431,13 +2543,29 @@ impl<A: hal::Api> Device<A> {
 
     /// Wait for idle and remove resources that we can, before we die.
     pub(crate) fn prepare_to_die(&mut self) {
+        if self.pending_writes.is_active
+            && self.unsubmitted_writes != queue::UnsubmittedWrites::Discard
+        {
+            log::warn!("Device is dropped with unsubmitted writes, discarding them");
+        }
         self.pending_writes.deactivate();
+        if let Some(failed) = self.failed_submission.take() {
+            log::warn!(
+                "Device is dropped with submission {} never retried, discarding it",
+                failed.index()
+            );
+            for raw in failed.discard(&mut self.pending_writes) {
+                self.command_allocator
+                    .lock()
+                    .release_encoder(&self.raw, raw);
+            }
+        }
         let mut life_tracker = self.life_tracker.lock();
-        let current_index = self.active_submission_index;
-        if let Err(error) = unsafe { self.raw.wait(&self.fence, current_index, CLEANUP_WAIT_MS) } {
+        let current_index = self.last_submitted_index();
+        if let Err(error) = self.wait_for_gpu(current_index) {
             log::error!("failed to wait for the device: {:?}", error);
         }
-        life_tracker.triage_submissions(current_index, &self.command_allocator);
+        life_tracker.triage_submissions(current_index, &self.raw, &self.command_allocator);
         life_tracker.cleanup(&self.raw);
     }
 
@@ -2463,6 +2591,18 @@ impl<A: hal::Api> crate::hub::Resource for Device<A> {
 #[error("device is invalid")]
 pub struct InvalidDevice;
 
+/// Native handle of the fence a device signals on submission.
+///
+/// The fence is signaled with the submission index of each submission, as
+/// returned by `queue_last_submission_index`, once the GPU is done with it.
+#[derive(Clone, Copy, Debug)]
+pub enum ExternalFenceHandle {
+    /// Raw `VkSemaphore` of a timeline semaphore, on the device's `VkDevice`.
+    VulkanTimelineSemaphore(u64),
+    /// Raw `ID3D12Fence` pointer, without an added reference.
+    Dx12Fence(*mut std::ffi::c_void),
+}
+
 #[derive(Clone, Debug, Error)]
 pub enum DeviceError {
     #[error("parent device is invalid")]
@@ -3695,6 +3835,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 device.limits.clone(),
                 device.downlevel.clone(),
                 device.features,
+                device.copy_debug_markers,
                 #[cfg(feature = "trace")]
                 device.trace.is_some(),
                 &desc.label,
@@ -4315,6 +4456,8 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 num_frames,
                 acquired_texture: None,
                 active_submission_index: 0,
+                acquired_frames: 0,
+                presented_frames: 0,
                 marker: PhantomData,
             };
             swap_chain_guard.insert(sc_id, swap_chain);
@@ -4326,6 +4469,22 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         (sc_id, Some(error))
     }
 
+    /// Returns the number of command encoders created by the device so far.
+    ///
+    /// This is meant for benchmarks checking that the encoders are reused.
+    #[cfg(feature = "testing")]
+    pub fn device_created_command_encoders<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+    ) -> Result<u64, InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(device_id).map_err(|_| InvalidDevice)?;
+        let created = device.command_allocator.lock().created;
+        Ok(created)
+    }
+
     #[cfg(feature = "replay")]
     /// Only triange suspected resource IDs. This helps us to avoid ID collisions
     /// upon creating new resources when re-playing a trace.
@@ -4365,6 +4524,47 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         Ok(())
     }
 
+    /// Returns `true` if the GPU is done with the submission of the given index,
+    /// as returned by `queue_last_submission_index`.
+    ///
+    /// This reads the current value of the device fence without blocking, but
+    /// doesn't do any maintenance: resources aren't freed and map callbacks
+    /// aren't fired until `device_poll` is called.
+    pub fn device_is_submission_complete<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        index: SubmissionIndex,
+    ) -> Result<bool, DeviceError> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard
+            .get(device_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let last_done = unsafe { device.raw.get_fence_value(&device.fence)? };
+        Ok(last_done >= index)
+    }
+
+    /// Returns the native fence the device signals on submission, so that
+    /// external code on the same native device can wait for wgpu submissions.
+    ///
+    /// Only Vulkan devices with timeline semaphores and DX12 devices support
+    /// this, `None` is returned otherwise.
+    ///
+    /// The handle is owned by the device and is only valid until the device is
+    /// dropped. External code may wait on the fence, but must never signal it
+    /// or otherwise change its value.
+    pub fn device_fence_handle<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+    ) -> Result<Option<ExternalFenceHandle>, InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(device_id).map_err(|_| InvalidDevice)?;
+        Ok(A::fence_handle(&device.fence))
+    }
+
     fn poll_devices<A: HalApi>(
         &self,
         force_wait: bool,
@@ -4434,6 +4634,23 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
 
         let hub = A::hub(self);
         let mut token = Token::root();
+
+        let flush = {
+            let (device_guard, _) = hub.devices.read(&mut token);
+            match device_guard.get(device_id) {
+                Ok(device) => {
+                    device.pending_writes.is_active
+                        && device.unsubmitted_writes == queue::UnsubmittedWrites::Flush
+                }
+                Err(_) => false,
+            }
+        };
+        if flush {
+            if let Err(error) = self.queue_submit::<A>(device_id, &[]) {
+                log::error!("Failed to submit the pending writes: {:?}", error);
+            }
+        }
+
         let (device, _) = hub.devices.unregister(device_id, &mut token);
         if let Some(mut device) = device {
             device.prepare_to_die();
@@ -4635,9 +4852,17 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                     buffer: &stage_buffer,
                     usage: hal::BufferUses::MAP_WRITE..hal::BufferUses::COPY_SRC,
                 };
+                let previous_usage = if device
+                    .pending_writes
+                    .track_buffer_write(buffer_id, 0..buffer.size)
+                {
+                    hal::BufferUses::COPY_DST
+                } else {
+                    hal::BufferUses::empty()
+                };
                 let transition_dst = hal::BufferBarrier {
                     buffer: raw_buf,
-                    usage: hal::BufferUses::empty()..hal::BufferUses::COPY_DST,
+                    usage: previous_usage..hal::BufferUses::COPY_DST,
                 };
                 let encoder = device.pending_writes.activate();
                 unsafe {
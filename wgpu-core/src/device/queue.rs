@@ -1,23 +1,35 @@
 #[cfg(feature = "trace")]
-use crate::device::trace::Action;
+use crate::device::trace::{self, Action};
 use crate::{
     command::{
-        extract_texture_selector, validate_linear_texture_data, validate_texture_copy_range,
-        CommandBuffer, CopySide, ImageCopyTexture, TransferError,
+        copy_debug_marker, estimate_transfer_ns, extract_texture_selector, validate_buffer_range,
+        validate_linear_texture_data, validate_texture_copy_range, CommandBuffer, CopySide,
+        ImageCopyTexture, TransferError,
     },
     conv,
-    device::{DeviceError, WaitIdleError},
-    hub::{Global, GlobalIdentityHandlerFactory, HalApi, Storage, Token},
-    id,
-    memory_init_tracker::{MemoryInitKind, MemoryInitTrackerAction},
-    resource::{Buffer, BufferAccessError, BufferMapState},
-    FastHashMap, FastHashSet,
+    device::{DeviceError, InvalidDevice, WaitIdleError},
+    hub::{Global, GlobalIdentityHandlerFactory, HalApi, Hub, Storage, Token},
+    id::{self, TypedId as _},
+    memory_init_tracker::{MemoryInitKind, MemoryInitTracker, MemoryInitTrackerAction},
+    resource::{Buffer, BufferAccessError, BufferInitPolicy, BufferMapState, Texture},
+    track::TextureSelector,
+    Epoch, FastHashMap, FastHashSet, LifeGuard, SubmissionIndex,
 };
 
 use hal::{CommandEncoder as _, Device as _, Queue as _};
 use parking_lot::Mutex;
 use smallvec::SmallVec;
-use std::{iter, mem, num::NonZeroU32, ops::Range, ptr};
+use std::{
+    future::Future,
+    iter, mem,
+    num::NonZeroU32,
+    ops::Range,
+    pin::Pin,
+    slice,
+    sync::{atomic::Ordering, Arc},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
 use thiserror::Error;
 
 /// Number of command buffers that we generate from the same pool
@@ -27,24 +39,342 @@ use thiserror::Error;
 /// without a concrete moment of when it can be cleared.
 const WRITE_COMMAND_BUFFERS_PER_POOL: usize = 64;
 
+/// Number of rows that `queue_write_texture` repacks into the staging
+/// buffer before flushing them, when the mapping isn't coherent.
+const REPACK_CHUNK_ROWS: u32 = 256;
+
+/// Maximum number of staging buffers kept around for reuse.
+const STAGING_POOL_SIZE: usize = 32;
+
+/// Staging buffers larger than this are destroyed after use instead of being
+/// kept for reuse, so that a single large upload doesn't pin its memory.
+const STAGING_POOL_MAX_BUFFER_SIZE: wgt::BufferAddress = 4 << 20;
+
+/// Time spent mapping, flushing and unmapping staging buffers, summed over
+/// all the uploads made on a device.
+///
+/// This is only measured with the `staging-stats` feature, and stays at zero otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StagingMapStats {
+    /// Number of times a staging buffer got mapped.
+    pub maps: u64,
+    pub map_time: Duration,
+    pub flush_time: Duration,
+    pub unmap_time: Duration,
+}
+
+impl StagingMapStats {
+    fn map<T>(&mut self, map: impl FnOnce() -> T) -> T {
+        if cfg!(feature = "staging-stats") {
+            self.maps += 1;
+        }
+        Self::measure(&mut self.map_time, map)
+    }
+
+    fn flush<T>(&mut self, flush: impl FnOnce() -> T) -> T {
+        Self::measure(&mut self.flush_time, flush)
+    }
+
+    fn unmap<T>(&mut self, unmap: impl FnOnce() -> T) -> T {
+        Self::measure(&mut self.unmap_time, unmap)
+    }
+
+    #[cfg(feature = "staging-stats")]
+    fn measure<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+        let start = std::time::Instant::now();
+        let result = f();
+        *total += start.elapsed();
+        result
+    }
+
+    #[cfg(not(feature = "staging-stats"))]
+    fn measure<T>(_total: &mut Duration, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+
+/// Largest copies that the queue writes perform at once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CopyLimits {
+    /// Maximum number of bytes copied from a buffer by a single copy.
+    pub max_buffer_copy_bytes: wgt::BufferAddress,
+    /// Maximum extent of a single copy into a texture. The depth applies to
+    /// the slices of 3D textures, as array layers are copied one by one.
+    ///
+    /// The texture writes split larger copies into several ones.
+    pub max_texture_copy_extent: wgt::Extent3d,
+}
+
+impl CopyLimits {
+    /// The backends don't report any limit on the size of buffer copies, and
+    /// texture copies are bounded by the texture dimensions only.
+    pub(super) fn from_limits(limits: &wgt::Limits) -> Self {
+        Self {
+            max_buffer_copy_bytes: wgt::BufferAddress::MAX,
+            max_texture_copy_extent: wgt::Extent3d {
+                width: limits
+                    .max_texture_dimension_1d
+                    .max(limits.max_texture_dimension_2d)
+                    .max(limits.max_texture_dimension_3d),
+                height: limits
+                    .max_texture_dimension_2d
+                    .max(limits.max_texture_dimension_3d),
+                depth_or_array_layers: limits.max_texture_dimension_3d,
+            },
+        }
+    }
+
+    fn check_buffer_copy(&self, size: wgt::BufferAddress) -> Result<(), TransferError> {
+        if size > self.max_buffer_copy_bytes {
+            return Err(TransferError::BufferCopyTooLarge {
+                size,
+                max: self.max_buffer_copy_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Layout of the rows of a texture write, in the source data and in the staging buffer.
+struct TextureStaging {
+    /// Row pitch of the source data.
+    bytes_per_row: u32,
+    /// Number of bytes of texel data in each row.
+    bytes_in_row: u32,
+    /// Row pitch in the staging buffer.
+    stage_bytes_per_row: u32,
+    height_blocks: u32,
+    block_rows_per_image: u32,
+    /// Whether the rows are repacked by the GPU, see `WriteTextureRepack::GpuCopy`.
+    gpu_repack: bool,
+}
+
+impl TextureStaging {
+    #[cfg(feature = "trace")]
+    fn layout(&self) -> StagingLayout {
+        StagingLayout {
+            bytes_per_row: self.stage_bytes_per_row,
+            rows_per_image: self.block_rows_per_image,
+            gpu_repack: self.gpu_repack,
+        }
+    }
+
+    /// Stages the write with `layout` instead, if the copies allow it.
+    ///
+    /// `row_pitch_alignment` is the alignment the texture copies require for
+    /// the row pitch of the stage.
+    fn with_layout(
+        mut self,
+        layout: &StagingLayout,
+        row_pitch_alignment: u32,
+    ) -> Result<Self, QueueWriteError> {
+        // Repacking on the GPU copies the rows between buffers.
+        let gpu_copyable = self.bytes_per_row as wgt::BufferAddress % wgt::COPY_BUFFER_ALIGNMENT
+            == 0
+            && self.bytes_in_row as wgt::BufferAddress % wgt::COPY_BUFFER_ALIGNMENT == 0;
+        // The images are as many rows apart in the stage as in the source.
+        if layout.bytes_per_row % row_pitch_alignment != 0
+            || layout.bytes_per_row < self.bytes_in_row
+            || layout.rows_per_image != self.block_rows_per_image
+            || (layout.gpu_repack && !gpu_copyable)
+        {
+            return Err(QueueWriteError::InvalidStagingLayout(layout.clone()));
+        }
+        self.stage_bytes_per_row = layout.bytes_per_row;
+        self.gpu_repack = layout.gpu_repack;
+        Ok(self)
+    }
+}
+
+/// How the data of a texture write is laid out in the staging buffer.
+///
+/// The copy regions follow from it: one per array layer, at a stride of
+/// `bytes_per_row * rows_per_image` bytes.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "trace", derive(serde::Serialize))]
+#[cfg_attr(feature = "replay", derive(serde::Deserialize))]
+pub struct StagingLayout {
+    /// Row pitch in the staging buffer.
+    pub bytes_per_row: u32,
+    /// Number of block rows of each image in the staging buffer.
+    pub rows_per_image: u32,
+    /// Whether the rows are uploaded as is, and repacked by the GPU.
+    pub gpu_repack: bool,
+}
+
+/// Describes the staging buffer of an upload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StagingInfo {
+    /// Number of bytes staged.
+    pub size: wgt::BufferAddress,
+    /// Size of the staging buffer, which is larger than `size` if it was
+    /// recycled from a larger upload.
+    pub capacity: wgt::BufferAddress,
+    /// Whether the buffer was recycled from the staging pool.
+    pub pooled: bool,
+}
+
+/// A staging buffer, and the number of bytes it holds.
+///
+/// A buffer recycled from the `StagingPool` can be larger than the upload
+/// using it, in which case `capacity` exceeds `size`.
 struct StagingData<A: hal::Api> {
     buffer: A::Buffer,
+    size: wgt::BufferAddress,
+    capacity: wgt::BufferAddress,
+}
+
+/// A staging buffer in the pool, tagged with the last submission using it.
+#[derive(Debug)]
+struct PooledStage<B> {
+    buffer: B,
+    capacity: wgt::BufferAddress,
+    submit_index: SubmissionIndex,
+}
+
+/// Staging buffers kept around for reuse by later uploads.
+///
+/// A buffer returned to the pool is in use by the pending writes, until they
+/// get submitted. It is then tagged with the index of that submission, and is
+/// only handed out again once the fence has reached this index.
+#[derive(Debug)]
+struct StagingPool<B> {
+    /// Buffers used by the pending writes, with their capacity.
+    pending: Vec<(B, wgt::BufferAddress)>,
+    submitted: Vec<PooledStage<B>>,
+}
+
+impl<B> StagingPool<B> {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            submitted: Vec::new(),
+        }
+    }
+
+    fn has_submitted(&self) -> bool {
+        !self.submitted.is_empty()
+    }
+
+    /// Take out the smallest buffer of at least `size` bytes, that isn't used
+    /// by any submission after `last_done`.
+    fn acquire(
+        &mut self,
+        size: wgt::BufferAddress,
+        last_done: SubmissionIndex,
+    ) -> Option<(B, wgt::BufferAddress)> {
+        let (index, _) = self
+            .submitted
+            .iter()
+            .enumerate()
+            .filter(|&(_, stage)| stage.submit_index <= last_done && stage.capacity >= size)
+            .min_by_key(|&(_, stage)| stage.capacity)?;
+        let stage = self.submitted.swap_remove(index);
+        Some((stage.buffer, stage.capacity))
+    }
+
+    /// Return a buffer used by the pending writes to the pool.
+    ///
+    /// If the pool can't take it, the buffer is given back to the caller.
+    fn release(&mut self, buffer: B, capacity: wgt::BufferAddress) -> Option<B> {
+        if capacity > STAGING_POOL_MAX_BUFFER_SIZE
+            || self.pending.len() + self.submitted.len() >= STAGING_POOL_SIZE
+        {
+            return Some(buffer);
+        }
+        self.pending.push((buffer, capacity));
+        None
+    }
+
+    /// Tag the buffers used by the pending writes with their submission.
+    fn submit(&mut self, submit_index: SubmissionIndex) {
+        self.submitted.extend(
+            self.pending
+                .drain(..)
+                .map(|(buffer, capacity)| PooledStage {
+                    buffer,
+                    capacity,
+                    submit_index,
+                }),
+        );
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = B> + '_ {
+        self.pending
+            .drain(..)
+            .map(|(buffer, _)| buffer)
+            .chain(self.submitted.drain(..).map(|stage| stage.buffer))
+    }
+}
+
+/// Returns `len` bytes at `offset` of a buffer mapping of `mapping_size` bytes.
+///
+/// All the writes into mappings go through here, so that they are bounds
+/// checked against the mapped range, instead of trusting the raw pointer.
+///
+/// # Safety
+///
+/// `mapping` has to be valid for writes of `mapping_size` bytes for `'a`, and
+/// not be accessed in any other way meanwhile.
+unsafe fn mapped_slice<'a>(
+    mapping: &'a hal::BufferMapping,
+    mapping_size: wgt::BufferAddress,
+    offset: wgt::BufferAddress,
+    len: usize,
+) -> &'a mut [u8] {
+    let end = offset.checked_add(len as wgt::BufferAddress);
+    assert!(
+        matches!(end, Some(end) if end <= mapping_size),
+        "{} bytes at offset {} are out of bounds of the mapping of {} bytes",
+        len,
+        offset,
+        mapping_size
+    );
+    slice::from_raw_parts_mut(mapping.ptr.as_ptr().add(offset as usize), len)
+}
+
+/// Copies `src` at `offset` of a buffer mapping of `mapping_size` bytes.
+///
+/// # Safety
+///
+/// Same as [`mapped_slice`]. In particular, `src` can't overlap the mapping.
+unsafe fn copy_into_mapping(
+    mapping: &hal::BufferMapping,
+    mapping_size: wgt::BufferAddress,
+    offset: wgt::BufferAddress,
+    src: &[u8],
+) {
+    mapped_slice(mapping, mapping_size, offset, src.len()).copy_from_slice(src);
 }
 
 impl<A: hal::Api> StagingData<A> {
     unsafe fn write(
         &self,
         device: &A::Device,
+        stats: &mut StagingMapStats,
         offset: wgt::BufferAddress,
         data: &[u8],
-    ) -> Result<(), hal::DeviceError> {
-        let mapping = device.map_buffer(&self.buffer, offset..offset + data.len() as u64)?;
-        ptr::copy_nonoverlapping(data.as_ptr(), mapping.ptr.as_ptr(), data.len());
+    ) -> Result<(), QueueWriteError> {
+        let end = match offset.checked_add(data.len() as wgt::BufferAddress) {
+            Some(end) if end <= self.size => end,
+            _ => {
+                return Err(QueueWriteError::StagingOverrun {
+                    offset,
+                    size: data.len() as wgt::BufferAddress,
+                    stage_size: self.size,
+                })
+            }
+        };
+        let mapping = stats
+            .map(|| device.map_buffer(&self.buffer, 0..self.size))
+            .map_err(DeviceError::from)?;
+        copy_into_mapping(&mapping, self.size, offset, data);
         if !mapping.is_coherent {
-            device
-                .flush_mapped_ranges(&self.buffer, iter::once(offset..offset + data.len() as u64));
+            stats.flush(|| device.flush_mapped_ranges(&self.buffer, iter::once(offset..end)));
         }
-        device.unmap_buffer(&self.buffer)?;
+        stats
+            .unmap(|| device.unmap_buffer(&self.buffer))
+            .map_err(DeviceError::from)?;
         Ok(())
     }
 }
@@ -68,6 +398,169 @@ impl<A: hal::Api> EncoderInFlight<A> {
     }
 }
 
+/// A submission that the queue ran out of memory for.
+///
+/// Its command buffers are kept baked, so that it can be retried with
+/// `queue_retry_submission` once some memory is freed. Work submitted in the
+/// meantime is appended to it, under the same index, and retries it as well.
+pub(super) struct FailedSubmission<A: hal::Api> {
+    index: SubmissionIndex,
+    executions: Vec<EncoderInFlight<A>>,
+    /// Resources destroyed by the user before the submission.
+    temp_resources: Vec<TempResource<A>>,
+    /// Positions in `executions` in front of which a command buffer of the
+    /// pending writes goes, in order. These command buffers are the last ones
+    /// of `PendingWrites::executing_command_buffers`.
+    pending_writes: Vec<usize>,
+}
+
+impl<A: hal::Api> FailedSubmission<A> {
+    fn new(index: SubmissionIndex) -> Self {
+        Self {
+            index,
+            executions: Vec::new(),
+            temp_resources: Vec::new(),
+            pending_writes: Vec::new(),
+        }
+    }
+
+    /// Append the work of a submission, to be submitted after the work
+    /// already in there.
+    fn append(
+        &mut self,
+        pending_writes: bool,
+        executions: Vec<EncoderInFlight<A>>,
+        temp_resources: Vec<TempResource<A>>,
+    ) {
+        if pending_writes {
+            self.pending_writes.push(self.executions.len());
+        }
+        self.executions.extend(executions);
+        self.temp_resources.extend(temp_resources);
+    }
+
+    /// List the command buffers to submit, given the executing command
+    /// buffers of the pending writes.
+    fn command_buffers<'a>(
+        &'a self,
+        write_cmd_buffers: &'a [A::CommandBuffer],
+    ) -> Vec<&'a A::CommandBuffer> {
+        let first_write = write_cmd_buffers.len() - self.pending_writes.len();
+        interleave_command_buffers(
+            &write_cmd_buffers[first_write..],
+            &self.pending_writes,
+            self.executions
+                .iter()
+                .map(|execution| execution.cmd_buffers.as_slice()),
+        )
+    }
+
+    /// Submit the work with `submit`, or keep it in `slot` for a retry if
+    /// that runs out of memory.
+    fn submit(
+        self,
+        slot: &mut Option<Self>,
+        write_cmd_buffers: &[A::CommandBuffer],
+        submit: impl FnOnce(&[&A::CommandBuffer], SubmissionIndex) -> Result<(), hal::DeviceError>,
+    ) -> Result<Self, QueueSubmitError> {
+        let result = submit(&self.command_buffers(write_cmd_buffers), self.index);
+        match result {
+            Ok(()) => Ok(self),
+            Err(error) => Err(self.keep_if_recoverable(slot, error.into())),
+        }
+    }
+
+    /// Keep the submission in `slot` if retrying it can recover from `error`,
+    /// and return the error to report for it.
+    fn keep_if_recoverable(self, slot: &mut Option<Self>, error: DeviceError) -> QueueSubmitError {
+        match error {
+            DeviceError::OutOfMemory => {
+                let index = self.index;
+                *slot = Some(self);
+                QueueSubmitError::OutOfMemory(index)
+            }
+            other => other.into(),
+        }
+    }
+
+    pub(super) fn index(&self) -> SubmissionIndex {
+        self.index
+    }
+
+    /// Reset the encoders of a submission that is never going to be retried.
+    ///
+    /// Its temporary resources are handed back to `pending_writes`, to be
+    /// destroyed with the rest.
+    pub(super) fn discard(
+        self,
+        pending_writes: &mut PendingWrites<A>,
+    ) -> impl Iterator<Item = A::CommandEncoder> {
+        pending_writes.temp_resources.extend(self.temp_resources);
+        self.executions
+            .into_iter()
+            .map(|execution| unsafe { execution.land() })
+    }
+}
+
+/// Put each of the `write_cmd_buffers` in front of the execution at the same
+/// index of `positions`, or after all of them if there is none.
+fn interleave_command_buffers<'a, T>(
+    write_cmd_buffers: &'a [T],
+    positions: &[usize],
+    executions: impl Iterator<Item = &'a [T]>,
+) -> Vec<&'a T> {
+    let mut writes = positions.iter().zip(write_cmd_buffers).peekable();
+    let mut list = Vec::new();
+    for (index, cmd_buffers) in executions.enumerate() {
+        while let Some(&(&position, cmd_buf)) = writes.peek() {
+            if position != index {
+                break;
+            }
+            list.push(cmd_buf);
+            writes.next();
+        }
+        list.extend(cmd_buffers);
+    }
+    list.extend(writes.map(|(_, cmd_buf)| cmd_buf));
+    list
+}
+
+/// Buffer ranges written since the last barrier on each buffer.
+#[derive(Debug, Default)]
+struct BufferWriteRanges {
+    map: FastHashMap<id::BufferId, Vec<Range<wgt::BufferAddress>>>,
+}
+
+impl BufferWriteRanges {
+    /// Returns `true` if the write overlaps a previous one that isn't
+    /// separated from it by a barrier.
+    fn track_write(&mut self, buffer_id: id::BufferId, range: Range<wgt::BufferAddress>) -> bool {
+        let ranges = self.map.entry(buffer_id).or_default();
+        let overlaps = ranges
+            .iter()
+            .any(|other| other.start < range.end && range.start < other.end);
+        if overlaps {
+            // the barrier orders all the previous writes
+            ranges.clear();
+        }
+        ranges.push(range);
+        overlaps
+    }
+
+    fn track_barrier(&mut self, buffer_id: id::BufferId) {
+        if let Some(ranges) = self.map.get_mut(&buffer_id) {
+            ranges.clear();
+        }
+    }
+}
+
+/// Commands recorded by the `queue_write_*` functions, to be executed
+/// before the command buffers of the next submission.
+///
+/// The writes are recorded on the same queue as everything else, and are
+/// submitted in the same batch, ahead of the user command buffers. This is
+/// what gives them their ordering guarantees: a write is visible to all the
+/// command buffers submitted after it, without any extra synchronization.
 #[derive(Debug)]
 pub(crate) struct PendingWrites<A: hal::Api> {
     pub command_encoder: A::CommandEncoder,
@@ -75,7 +568,11 @@ pub(crate) struct PendingWrites<A: hal::Api> {
     pub temp_resources: Vec<TempResource<A>>,
     pub dst_buffers: FastHashSet<id::BufferId>,
     pub dst_textures: FastHashSet<id::TextureId>,
+    dst_buffer_ranges: BufferWriteRanges,
     pub executing_command_buffers: Vec<A::CommandBuffer>,
+    /// Index of the last submission that carried writes, or 0 if there was none.
+    last_submission: SubmissionIndex,
+    staging_pool: StagingPool<A::Buffer>,
 }
 
 impl<A: hal::Api> PendingWrites<A> {
@@ -86,7 +583,10 @@ impl<A: hal::Api> PendingWrites<A> {
             temp_resources: Vec::new(),
             dst_buffers: FastHashSet::default(),
             dst_textures: FastHashSet::default(),
+            dst_buffer_ranges: BufferWriteRanges::default(),
             executing_command_buffers: Vec::new(),
+            last_submission: 0,
+            staging_pool: StagingPool::new(),
         }
     }
 
@@ -100,6 +600,11 @@ impl<A: hal::Api> PendingWrites<A> {
             device.destroy_command_encoder(self.command_encoder);
         }
 
+        for buffer in self.staging_pool.drain() {
+            unsafe {
+                device.destroy_buffer(buffer);
+            }
+        }
         for resource in self.temp_resources {
             match resource {
                 TempResource::Buffer(buffer) => unsafe {
@@ -116,17 +621,43 @@ impl<A: hal::Api> PendingWrites<A> {
         self.temp_resources.push(resource);
     }
 
+    /// Keep a staging buffer alive until the pending writes are done with it.
+    ///
+    /// The buffer goes back to the staging pool if there is room for it.
     fn consume(&mut self, stage: StagingData<A>) {
-        self.temp_resources.push(TempResource::Buffer(stage.buffer));
+        if let Some(buffer) = self.staging_pool.release(stage.buffer, stage.capacity) {
+            self.temp_resources.push(TempResource::Buffer(buffer));
+        }
+    }
+
+    /// Record a write to `range` of the buffer.
+    ///
+    /// Returns `true` if the write overlaps a previous one that isn't yet
+    /// separated from it by a barrier, in which case the caller has to insert one.
+    pub fn track_buffer_write(
+        &mut self,
+        buffer_id: id::BufferId,
+        range: Range<wgt::BufferAddress>,
+    ) -> bool {
+        self.dst_buffer_ranges.track_write(buffer_id, range)
+    }
+
+    /// Record a barrier transitioning the buffer, which orders all the
+    /// previous writes to it, so that the next write doesn't need another one.
+    pub fn track_buffer_barrier(&mut self, buffer_id: id::BufferId) {
+        self.dst_buffer_ranges.track_barrier(buffer_id);
     }
 
     #[must_use]
-    fn pre_submit(&mut self) -> Option<&A::CommandBuffer> {
+    fn pre_submit(&mut self, submit_index: SubmissionIndex) -> Option<&A::CommandBuffer> {
+        self.staging_pool.submit(submit_index);
         self.dst_buffers.clear();
         self.dst_textures.clear();
+        self.dst_buffer_ranges.map.clear();
         if self.is_active {
             let cmd_buf = unsafe { self.command_encoder.end_encoding().unwrap() };
             self.is_active = false;
+            self.last_submission = submit_index;
             self.executing_command_buffers.push(cmd_buf);
             self.executing_command_buffers.last()
         } else {
@@ -192,35 +723,262 @@ impl RequiredBufferInits {
             let buffer = buffer_guard
                 .get_mut(buffer_use.id)
                 .map_err(|_| QueueSubmitError::DestroyedBuffer(buffer_use.id))?;
+            self.add_action(
+                buffer_use,
+                &mut buffer.initialization_status,
+                buffer.size,
+                buffer.init_policy,
+            );
+        }
+        Ok(())
+    }
 
-            let uninitialized_ranges = buffer.initialization_status.drain(buffer_use.range.clone());
-            match buffer_use.kind {
-                MemoryInitKind::ImplicitlyInitialized => {
-                    uninitialized_ranges.for_each(drop);
-                }
-                MemoryInitKind::NeedsInitializedMemory => {
-                    self.map
-                        .entry(buffer_use.id)
-                        .or_default()
-                        .extend(uninitialized_ranges);
-                }
+    fn add_action(
+        &mut self,
+        buffer_use: &MemoryInitTrackerAction<id::BufferId>,
+        initialization_status: &mut MemoryInitTracker,
+        buffer_size: wgt::BufferAddress,
+        init_policy: BufferInitPolicy,
+    ) {
+        // Buffers that skip initialization treat every use as a write.
+        let kind = match init_policy {
+            BufferInitPolicy::Zero => buffer_use.kind,
+            BufferInitPolicy::Skip => MemoryInitKind::ImplicitlyInitialized,
+        };
+
+        // A write covering the whole buffer doesn't need the ranges it overwrites.
+        if let MemoryInitKind::ImplicitlyInitialized = kind {
+            if buffer_use.range.start == 0 && buffer_use.range.end >= buffer_size {
+                initialization_status.clear_all();
+                return;
+            }
+        }
+
+        let uninitialized_ranges = initialization_status.drain(buffer_use.range.clone());
+        match kind {
+            MemoryInitKind::ImplicitlyInitialized => {
+                uninitialized_ranges.for_each(drop);
+            }
+            MemoryInitKind::NeedsInitializedMemory => {
+                self.map
+                    .entry(buffer_use.id)
+                    .or_default()
+                    .extend(uninitialized_ranges);
             }
         }
-        Ok(())
     }
 }
 
 impl<A: hal::Api> super::Device<A> {
+    /// Alignment of the rows of `format` texture data in staging buffers.
+    fn bytes_per_row_alignment(&self, format: wgt::TextureFormat) -> u32 {
+        get_lowest_common_denom(
+            self.alignments.buffer_copy_pitch.get() as u32,
+            format.describe().block_size as u32,
+        )
+    }
+
+    /// Returns how `queue_write_texture` lays out `size` texels of `format`
+    /// in the staging buffer, given the layout of the source data.
+    fn texture_staging(
+        &self,
+        format: wgt::TextureFormat,
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> TextureStaging {
+        let format_desc = format.describe();
+        let (block_width, block_height) = format_desc.block_dimensions;
+        let width_blocks = size.width / block_width as u32;
+        let height_blocks = size.height / block_height as u32;
+
+        let block_rows_per_image = match data_layout.rows_per_image {
+            Some(rows_per_image) => rows_per_image.get(),
+            None => {
+                // doesn't really matter because we need this only if we copy more than one layer, and then we validate for this being not None
+                size.height
+            }
+        };
+
+        let bytes_in_row = format_desc.block_size as u32 * width_blocks;
+        let stage_bytes_per_row = align_to(bytes_in_row, self.bytes_per_row_alignment(format));
+        let bytes_per_row = if let Some(bytes_per_row) = data_layout.bytes_per_row {
+            bytes_per_row.get()
+        } else {
+            bytes_in_row
+        };
+
+        // The GPU can only repack rows that it's able to copy between buffers.
+        let gpu_repack = stage_bytes_per_row != bytes_per_row
+            && self.write_texture_repack == WriteTextureRepack::GpuCopy
+            && bytes_per_row as wgt::BufferAddress % wgt::COPY_BUFFER_ALIGNMENT == 0
+            && bytes_in_row as wgt::BufferAddress % wgt::COPY_BUFFER_ALIGNMENT == 0;
+
+        TextureStaging {
+            bytes_per_row,
+            bytes_in_row,
+            stage_bytes_per_row,
+            height_blocks,
+            block_rows_per_image,
+            gpu_repack,
+        }
+    }
+
+    /// Like `texture_staging`, for a texture write of `queue_write_batch`.
+    ///
+    /// The batch shares one mapped staging buffer between all of its writes,
+    /// so the rows are always repacked while filling it.
+    fn batch_texture_staging(
+        &self,
+        format: wgt::TextureFormat,
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> TextureStaging {
+        TextureStaging {
+            gpu_repack: false,
+            ..self.texture_staging(format, data_layout, size)
+        }
+    }
+
+    /// Get a buffer for staging the data of a single write.
+    ///
+    /// A buffer from the staging pool is reused if the GPU is done with it,
+    /// otherwise a new one is created. The usage is kept to the minimum the
+    /// uploads need.
     fn prepare_stage(&mut self, size: wgt::BufferAddress) -> Result<StagingData<A>, DeviceError> {
         profiling::scope!("prepare_stage");
+        if self.pending_writes.staging_pool.has_submitted() {
+            let last_done = unsafe { self.raw.get_fence_value(&self.fence)? };
+            if let Some((buffer, capacity)) =
+                self.pending_writes.staging_pool.acquire(size, last_done)
+            {
+                #[cfg(feature = "staging-stats")]
+                self.last_staging = Some(StagingInfo {
+                    size,
+                    capacity,
+                    pooled: true,
+                });
+                return Ok(StagingData {
+                    buffer,
+                    size,
+                    capacity,
+                });
+            }
+        }
         let stage_desc = hal::BufferDescriptor {
             label: Some("_Staging"),
             size,
             usage: hal::BufferUses::MAP_WRITE | hal::BufferUses::COPY_SRC,
+            memory_flags: hal::MemoryFlags::empty(),
+        };
+        let buffer = unsafe { self.raw.create_buffer(&stage_desc)? };
+        #[cfg(feature = "staging-stats")]
+        self.last_staging = Some(StagingInfo {
+            size,
+            capacity: size,
+            pooled: false,
+        });
+        Ok(StagingData {
+            buffer,
+            size,
+            capacity: size,
+        })
+    }
+
+    /// Returns the index of the last submission using a resource, if the GPU
+    /// may still be executing it.
+    ///
+    /// Uses by the pending writes are at the next index, and don't count.
+    fn in_flight_use(
+        &self,
+        life_guard: &LifeGuard,
+    ) -> Result<Option<SubmissionIndex>, DeviceError> {
+        let last_use = life_guard.submission_index.load(Ordering::Acquire);
+        if last_use == 0 || last_use > self.active_submission_index {
+            return Ok(None);
+        }
+        let last_done = unsafe { self.raw.get_fence_value(&self.fence) }?;
+        Ok(if last_use > last_done {
+            Some(last_use)
+        } else {
+            None
+        })
+    }
+
+    /// Create a buffer that is only written and read by the GPU,
+    /// for repacking the staged rows of a texture write.
+    fn prepare_repack_stage(
+        &mut self,
+        size: wgt::BufferAddress,
+    ) -> Result<StagingData<A>, DeviceError> {
+        profiling::scope!("prepare_repack_stage");
+        let stage_desc = hal::BufferDescriptor {
+            label: Some("_Repack"),
+            size,
+            usage: hal::BufferUses::COPY_DST | hal::BufferUses::COPY_SRC,
             memory_flags: hal::MemoryFlags::TRANSIENT,
         };
         let buffer = unsafe { self.raw.create_buffer(&stage_desc)? };
-        Ok(StagingData { buffer })
+        Ok(StagingData {
+            buffer,
+            size,
+            capacity: size,
+        })
+    }
+
+    /// Record filling `ranges` of the buffer with zeros into the pending writes.
+    ///
+    /// The ranges are expected to be drained from the initialization status of
+    /// the buffer, and aligned to `COPY_BUFFER_ALIGNMENT`.
+    fn fill_buffer_ranges_with_zeros(
+        &mut self,
+        buffer_guard: &Storage<Buffer<A>, id::BufferId>,
+        buffer_id: id::BufferId,
+        ranges: Vec<Range<wgt::BufferAddress>>,
+    ) -> Result<(), TransferError> {
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        let mut trackers = self.trackers.lock();
+        let (buffer, transition) = trackers
+            .buffers
+            .use_replace(buffer_guard, buffer_id, (), hal::BufferUses::COPY_DST)
+            .map_err(TransferError::InvalidBuffer)?;
+        let raw_buf = buffer
+            .raw
+            .as_ref()
+            .ok_or(TransferError::InvalidBuffer(buffer_id))?;
+        buffer.life_guard.use_at(self.active_submission_index + 1);
+        let dst_barrier = transition.map(|pending| pending.into_hal(buffer)).next();
+        if dst_barrier.is_some() {
+            self.pending_writes.track_buffer_barrier(buffer_id);
+        }
+
+        // The filled ranges were never written to, but later writes may overlap them.
+        let mut needs_barrier = false;
+        for range in ranges.iter() {
+            needs_barrier |= self
+                .pending_writes
+                .track_buffer_write(buffer_id, range.clone());
+        }
+        let overlap_barrier = if needs_barrier {
+            Some(hal::BufferBarrier {
+                buffer: raw_buf,
+                usage: hal::BufferUses::COPY_DST..hal::BufferUses::COPY_DST,
+            })
+        } else {
+            None
+        };
+
+        let encoder = self.pending_writes.activate();
+        unsafe {
+            encoder.transition_buffers(dst_barrier.into_iter().chain(overlap_barrier));
+            for range in ranges {
+                encoder.fill_buffer(raw_buf, range, 0);
+            }
+        }
+        self.pending_writes.dst_buffers.insert(buffer_id);
+        Ok(())
     }
 
     fn initialize_buffer_memory(
@@ -263,9 +1021,7 @@ impl<A: hal::Api> super::Device<A> {
             }
 
             for range in ranges {
-                assert!(range.start % 4 == 0, "Buffer {:?} has an uninitialized range with a start not aligned to 4 (start was {})", raw_buf, range.start);
-                assert!(range.end % 4 == 0, "Buffer {:?} has an uninitialized range with an end not aligned to 4 (end was {})", raw_buf, range.end);
-
+                let range = align_init_range(range, buffer.size);
                 unsafe {
                     encoder.fill_buffer(raw_buf, range, 0);
                 }
@@ -274,6 +1030,69 @@ impl<A: hal::Api> super::Device<A> {
 
         Ok(())
     }
+
+    /// The index of the last submission that made it to the queue.
+    pub(super) fn last_submitted_index(&self) -> SubmissionIndex {
+        match self.failed_submission {
+            Some(ref failed) => failed.index - 1,
+            None => self.active_submission_index,
+        }
+    }
+
+    /// Register a submission that made it to the queue with the lifetime
+    /// tracker, and clean up after the work the GPU is done with.
+    fn finish_submission<'this, 'token: 'this, G: GlobalIdentityHandlerFactory>(
+        &'this mut self,
+        hub: &Hub<A, G>,
+        submit_index: SubmissionIndex,
+        mut active_executions: Vec<EncoderInFlight<A>>,
+        mut temp_resources: Vec<TempResource<A>>,
+        cleanup_mode: CleanupMode,
+        token: &mut Token<'token, Self>,
+    ) -> Result<(Vec<super::BufferMapPendingCallback>, SubmitCleanup), QueueSubmitError> {
+        profiling::scope!("cleanup");
+        if let Some(pending_execution) =
+            self.pending_writes
+                .post_submit(&self.command_allocator, &self.raw, &self.queue)
+        {
+            active_executions.push(pending_execution);
+        }
+
+        // this will register the new submission to the life time tracker
+        self.lock_life(token).track_submission(
+            submit_index,
+            temp_resources.drain(..),
+            active_executions,
+        );
+
+        // This will schedule destruction of all resources that are no longer needed
+        // by the user but used in the command stream, among other things.
+        let cleanup_budget_ms = match cleanup_mode {
+            CleanupMode::Full | CleanupMode::OneShot => None,
+            CleanupMode::Bounded(budget_ms) => Some(budget_ms),
+        };
+        let (callbacks, timed_out) =
+            match self.maintain_bounded(hub, false, cleanup_budget_ms, token) {
+                Ok(result) => result,
+                Err(WaitIdleError::Device(err)) => return Err(QueueSubmitError::Queue(err)),
+                Err(WaitIdleError::StuckGpu) => return Err(QueueSubmitError::StuckGpu),
+            };
+
+        // Reuse the allocation, unless resources got destroyed since a failed
+        // submission that is being retried.
+        if self.pending_writes.temp_resources.is_empty() {
+            self.pending_writes.temp_resources = temp_resources;
+        }
+        self.temp_suspected.clear();
+        self.lock_life(token).post_submit();
+
+        let cleanup = if timed_out {
+            SubmitCleanup::Partial
+        } else {
+            SubmitCleanup::Complete
+        };
+        Ok((callbacks, cleanup))
+    }
 }
 
 #[derive(Clone, Debug, Error)]
@@ -286,6 +1105,347 @@ pub enum QueueWriteError {
     Queue(#[from] DeviceError),
     #[error(transparent)]
     Transfer(#[from] TransferError),
+    #[error(
+        "buffer {buffer_id:?} is stale: expected epoch {expected}, but the slot holds {current:?}"
+    )]
+    StaleBuffer {
+        buffer_id: id::BufferId,
+        expected: Epoch,
+        current: Option<Epoch>,
+    },
+    #[error("write of {actual} bytes doesn't match the expected length of {expected} bytes")]
+    UnexpectedDataLength {
+        expected: wgt::BufferAddress,
+        actual: wgt::BufferAddress,
+    },
+    #[error("buffer can't be left in usage {0:?} after the write")]
+    InvalidNextUsage(hal::BufferUses),
+    #[error("rows of {bytes_per_row} bytes aren't padded to the staging row pitch of {stage_bytes_per_row} bytes")]
+    MismatchedRowPadding {
+        bytes_per_row: u32,
+        stage_bytes_per_row: u32,
+    },
+    #[error(
+        "buffer {buffer_id:?} is written while submission {submission_index} may still use it"
+    )]
+    WriteAfterRead {
+        buffer_id: id::BufferId,
+        submission_index: SubmissionIndex,
+    },
+    #[error("write of {size} bytes at offset {offset} overruns the staging buffer of {stage_size} bytes")]
+    StagingOverrun {
+        offset: wgt::BufferAddress,
+        size: wgt::BufferAddress,
+        stage_size: wgt::BufferAddress,
+    },
+    #[error("texture write can't be staged with {0:?}")]
+    InvalidStagingLayout(StagingLayout),
+    #[error("{what} of {value} pixels must be a multiple of the {block_width}x{block_height} blocks of {format:?}")]
+    UnalignedPixelRegion {
+        what: &'static str,
+        value: u32,
+        format: wgt::TextureFormat,
+        block_width: u8,
+        block_height: u8,
+    },
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum QueueTransitionError {
+    #[error(transparent)]
+    Queue(#[from] DeviceError),
+    #[error("buffer {0:?} is invalid or destroyed")]
+    InvalidBuffer(id::BufferId),
+    #[error("texture {0:?} is invalid or destroyed")]
+    InvalidTexture(id::TextureId),
+    #[error("buffer can't be transitioned to usage {0:?}")]
+    InvalidBufferUsage(hal::BufferUses),
+    #[error("texture can't be transitioned to usage {0:?}")]
+    InvalidTextureUsage(hal::TextureUses),
+}
+
+/// How `queue_submit_impl` cleans up after the work the GPU is done with.
+enum CleanupMode {
+    /// Destroy all the resources that are no longer used.
+    Full,
+    /// Like `Full`, but stop destroying resources after a number of milliseconds.
+    Bounded(u32),
+    /// Like `Full`, and also check every resource used by the submitted
+    /// command buffers for destruction, tying the ones dropped by the user
+    /// to the submission.
+    OneShot,
+}
+
+/// A single write of `queue_write_batch`.
+#[derive(Clone, Debug)]
+pub enum WriteOp<'a> {
+    Buffer {
+        buffer_id: id::BufferId,
+        offset: wgt::BufferAddress,
+        data: &'a [u8],
+    },
+    Texture {
+        destination: ImageCopyTexture,
+        data: &'a [u8],
+        layout: wgt::ImageDataLayout,
+        size: wgt::Extent3d,
+    },
+}
+
+/// Where the data of a `WriteOp` ends up in the shared staging buffer.
+enum StagedWrite {
+    Buffer {
+        stage_offset: wgt::BufferAddress,
+    },
+    Texture {
+        stage_offset: wgt::BufferAddress,
+        stage_size: wgt::BufferAddress,
+        selector: TextureSelector,
+        base: hal::TextureCopyBase,
+        copy_size: hal::CopyExtent,
+        array_layer_count: u32,
+        format_desc: wgt::TextureFormatInfo,
+        staging: TextureStaging,
+    },
+}
+
+/// How `queue_write_texture` handles source rows that don't have the pitch
+/// required for copying them into the texture.
+///
+/// There is deliberately no mode repacking on a thread pool. wgpu-core doesn't
+/// spawn threads, and the repacking writes into a staging buffer that is only
+/// mapped while the device is locked, so the pool would have to be joined
+/// before the write returns, holding up every other user of the device.
+/// Callers that want to repack off the calling thread can pad the rows on
+/// their own threads, and stage them with `queue_write_texture_prepadded`.
+///
+/// The `write_texture` benchmark of the player compares these strategies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteTextureRepack {
+    /// Repack the rows on the calling thread, while filling the staging buffer.
+    Inline,
+    /// Upload the rows as they are, and let the GPU copy them one by one into
+    /// a second buffer with the required pitch. This trades the CPU time of
+    /// repacking for an extra buffer and copy.
+    ///
+    /// Rows that aren't aligned to `COPY_BUFFER_ALIGNMENT` are repacked inline.
+    GpuCopy,
+}
+
+impl Default for WriteTextureRepack {
+    fn default() -> Self {
+        Self::Inline
+    }
+}
+
+/// What happens to the writes that are still pending, when a device is dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnsubmittedWrites {
+    /// Silently discard them.
+    Discard,
+    /// Discard them, but log a warning, so that a forgotten submission is noticed.
+    Warn,
+    /// Submit them before dropping the device.
+    Flush,
+}
+
+impl Default for UnsubmittedWrites {
+    fn default() -> Self {
+        Self::Discard
+    }
+}
+
+/// What `queue_write_buffer` does when the buffer may still be in use by a
+/// submission that the GPU hasn't finished yet.
+///
+/// The write is recorded in front of the next submission, so it has to wait
+/// on the GPU for that earlier work to be done with the buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteAfterRead {
+    /// Let the write wait for the earlier work.
+    Serialize,
+    /// Let the write wait, but log a warning, so that the stall is noticed.
+    Warn,
+    /// Refuse the write with `QueueWriteError::WriteAfterRead`.
+    Error,
+}
+
+impl Default for WriteAfterRead {
+    fn default() -> Self {
+        Self::Serialize
+    }
+}
+
+/// Convert the `size` in pixels of a region at `origin` of a mip level of
+/// `mip_size` pixels into the extent in whole blocks that the copies take.
+///
+/// The origin and size have to be multiples of the block dimensions, except
+/// for a size that reaches the edge of the mip level: it's rounded up to the
+/// blocks covering the edge.
+fn pixel_region_to_copy_extent(
+    format: wgt::TextureFormat,
+    mip_size: wgt::Extent3d,
+    origin: wgt::Origin3d,
+    size: wgt::Extent3d,
+) -> Result<wgt::Extent3d, QueueWriteError> {
+    let (block_width, block_height) = format.describe().block_dimensions;
+    let unaligned = |what, value| QueueWriteError::UnalignedPixelRegion {
+        what,
+        value,
+        format,
+        block_width,
+        block_height,
+    };
+    if origin.x % block_width as u32 != 0 {
+        return Err(unaligned("origin x", origin.x));
+    }
+    if origin.y % block_height as u32 != 0 {
+        return Err(unaligned("origin y", origin.y));
+    }
+    let to_blocks = |what, origin: u32, size: u32, mip_size: u32, block: u8| {
+        let block = block as u32;
+        match size % block {
+            0 => Ok(size),
+            rest if origin.checked_add(size) == Some(mip_size) => Ok(size + block - rest),
+            _ => Err(unaligned(what, size)),
+        }
+    };
+    Ok(wgt::Extent3d {
+        width: to_blocks("width", origin.x, size.width, mip_size.width, block_width)?,
+        height: to_blocks(
+            "height",
+            origin.y,
+            size.height,
+            mip_size.height,
+            block_height,
+        )?,
+        depth_or_array_layers: size.depth_or_array_layers,
+    })
+}
+
+/// Checks a write of `data_size` bytes at `offset` into the `dst` buffer.
+///
+/// `queue_write_batch` validates each of its buffer writes with this as well.
+fn validate_buffer_write<A: hal::Api>(
+    dst: &Buffer<A>,
+    buffer_id: id::BufferId,
+    offset: wgt::BufferAddress,
+    data_size: wgt::BufferAddress,
+    copy_limits: &CopyLimits,
+) -> Result<(), QueueWriteError> {
+    if dst.raw.is_none() {
+        return Err(TransferError::InvalidBuffer(buffer_id).into());
+    }
+    if !dst.usage.contains(wgt::BufferUsages::COPY_DST) {
+        return Err(TransferError::MissingCopyDstUsageFlag(Some(buffer_id), None).into());
+    }
+    if data_size % wgt::COPY_BUFFER_ALIGNMENT != 0 {
+        return Err(TransferError::UnalignedCopySize(data_size).into());
+    }
+    if offset % wgt::COPY_BUFFER_ALIGNMENT != 0 {
+        return Err(TransferError::UnalignedBufferOffset(offset).into());
+    }
+    validate_buffer_range(offset, data_size, dst.size, CopySide::Destination)?;
+    copy_limits.check_buffer_copy(data_size)?;
+    Ok(())
+}
+
+/// Destination of a texture write, as checked by `validate_texture_write`.
+struct TextureWriteTarget {
+    selector: TextureSelector,
+    base: hal::TextureCopyBase,
+    format: wgt::TextureFormat,
+    copy_size: hal::CopyExtent,
+    array_layer_count: u32,
+}
+
+/// Checks a write of `data_size` bytes laid out as `layout` into the
+/// `destination` texture.
+///
+/// `queue_write_batch` validates each of its texture writes with this as well.
+fn validate_texture_write<A: hal::Api>(
+    destination: &ImageCopyTexture,
+    data_size: wgt::BufferAddress,
+    layout: &wgt::ImageDataLayout,
+    size: &wgt::Extent3d,
+    texture_guard: &Storage<Texture<A>, id::TextureId>,
+) -> Result<TextureWriteTarget, QueueWriteError> {
+    let (selector, base, format) =
+        extract_texture_selector(destination, size, CopySide::Destination, texture_guard)?;
+    validate_linear_texture_data(
+        layout,
+        format,
+        data_size,
+        CopySide::Source,
+        format.describe().block_size as wgt::BufferAddress,
+        size,
+        false,
+    )?;
+    if !conv::is_valid_copy_dst_texture_format(format) {
+        return Err(TransferError::CopyToForbiddenTextureFormat(format).into());
+    }
+    let dst = &texture_guard[destination.texture];
+    if dst.raw.is_none() {
+        return Err(TransferError::InvalidTexture(destination.texture).into());
+    }
+    if !dst.desc.usage.contains(wgt::TextureUsages::COPY_DST) {
+        return Err(TransferError::MissingCopyDstUsageFlag(None, Some(destination.texture)).into());
+    }
+    let (copy_size, array_layer_count) =
+        validate_texture_copy_range(destination, &dst.desc, CopySide::Destination, size)?;
+    Ok(TextureWriteTarget {
+        selector,
+        base,
+        format,
+        copy_size,
+        array_layer_count,
+    })
+}
+
+/// Whether `queue_write_buffer` fills the buffer with zeros instead of
+/// staging `data`, given the threshold set with
+/// `device_set_zero_write_fill_threshold`.
+///
+/// The size is compared first, so that the data is only scanned when the
+/// write is large enough.
+fn is_zero_fill(data: &[u8], threshold: Option<wgt::BufferAddress>) -> bool {
+    match threshold {
+        Some(threshold) => {
+            data.len() as wgt::BufferAddress >= threshold && data.iter().all(|&byte| byte == 0)
+        }
+        None => false,
+    }
+}
+
+/// Check that the slot of `buffer_id`, currently holding the epoch `current`,
+/// still holds the `expected` epoch.
+///
+/// Only the slot is looked at, so the returned id refers to the buffer living
+/// there, even if `buffer_id` itself carries an older epoch.
+fn check_buffer_epoch(
+    buffer_id: id::BufferId,
+    expected: Epoch,
+    current: Option<Epoch>,
+) -> Result<id::BufferId, QueueWriteError> {
+    if current != Some(expected) {
+        return Err(QueueWriteError::StaleBuffer {
+            buffer_id,
+            expected,
+            current,
+        });
+    }
+    let (index, _, backend) = buffer_id.unzip();
+    Ok(id::TypedId::zip(index, expected, backend))
+}
+
+/// Outcome of the cleanup done by `queue_submit_with_timeout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubmitCleanup {
+    /// Everything the GPU is done with was cleaned up.
+    Complete,
+    /// The timeout elapsed before the cleanup was done. The remaining
+    /// resources are destroyed by a later maintenance pass.
+    Partial,
 }
 
 #[derive(Clone, Debug, Error)]
@@ -298,10 +1458,150 @@ pub enum QueueSubmitError {
     DestroyedTexture(id::TextureId),
     #[error(transparent)]
     Unmap(#[from] BufferAccessError),
-    #[error("swap chain output was dropped before the command buffer got submitted")]
-    SwapChainOutputDropped,
+    #[error("output of swap chain {0:?} was dropped without being presented before the command buffer got submitted")]
+    SwapChainOutputDropped(id::SwapChainId),
+    #[error("output of swap chain {0:?} was already presented, the command buffer rendering to it is stale")]
+    SwapChainOutputPresented(id::SwapChainId),
     #[error("GPU got stuck :(")]
     StuckGpu,
+    #[error("not enough memory left to execute submission {0}, it is retried with the next submission or `queue_retry_submission`")]
+    OutOfMemory(SubmissionIndex),
+    #[error("not enough memory left to encode the resource transitions, the command buffers can be submitted again")]
+    TransitOutOfMemory,
+    #[error("there is no failed submission to retry")]
+    NothingToRetry,
+}
+
+/// Statistics about the staging of a texture upload.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextureUploadStats {
+    /// Number of bytes of texel data in the upload.
+    pub tight_bytes: wgt::BufferAddress,
+    /// Number of bytes staged for the upload, with the rows padded to the
+    /// pitch the device copies from.
+    pub staged_bytes: wgt::BufferAddress,
+    /// Ratio of the staged bytes to the texel bytes, 1 when nothing is padded.
+    pub padding_ratio: f32,
+    /// Rough estimate of the GPU time the copy out of the staging buffer
+    /// takes, in nanoseconds. See [`estimate_transfer_ns`].
+    pub estimated_ns: u64,
+    /// Number of bytes of the staging buffers used for the upload. This is
+    /// more than the staged bytes if a larger buffer got reused, or if the
+    /// rows are repacked on the GPU, which takes a second buffer.
+    pub staging_capacity: wgt::BufferAddress,
+}
+
+impl TextureUploadStats {
+    fn new(tight_bytes: wgt::BufferAddress, staged_bytes: wgt::BufferAddress) -> Self {
+        Self {
+            tight_bytes,
+            staged_bytes,
+            padding_ratio: staged_bytes as f32 / tight_bytes as f32,
+            estimated_ns: estimate_transfer_ns(staged_bytes),
+            staging_capacity: staged_bytes,
+        }
+    }
+}
+
+/// Staging memory taken by a queue write, for memory accounting.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WriteAccounting {
+    /// Number of bytes of staging memory allocated for the write, including
+    /// the padding of the rows.
+    pub staged_bytes: wgt::BufferAddress,
+    /// Number of bytes of data copied into the destination.
+    pub copied_bytes: wgt::BufferAddress,
+}
+
+impl From<TextureUploadStats> for WriteAccounting {
+    fn from(stats: TextureUploadStats) -> Self {
+        Self {
+            staged_bytes: stats.staging_capacity,
+            copied_bytes: stats.tight_bytes,
+        }
+    }
+}
+
+/// A step of `queue_submit_ordered`.
+#[derive(Clone, Debug)]
+pub enum SubmitStep<'a> {
+    /// Writes executing after all the previous steps.
+    Writes(&'a [WriteOp<'a>]),
+    /// Command buffers executing after all the previous steps.
+    CommandBuffers(&'a [id::CommandBufferId]),
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum QueueSubmitOrderedError {
+    #[error(transparent)]
+    Write(#[from] QueueWriteError),
+    #[error(transparent)]
+    Submit(#[from] QueueSubmitError),
+}
+
+#[derive(Default)]
+struct WorkDoneState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// Completion of a submission, shared between the lifetime tracker
+/// and the future awaiting it.
+#[derive(Default)]
+pub(super) struct WorkDoneSignal {
+    state: Mutex<WorkDoneState>,
+}
+
+impl WorkDoneSignal {
+    pub(super) fn complete(&self) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.done = true;
+            state.waker.take()
+        };
+        // wake outside of the lock, in case the executor polls right away
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn poll(&self, waker: &Waker) -> bool {
+        let mut state = self.state.lock();
+        if !state.done {
+            state.waker = Some(waker.clone());
+        }
+        state.done
+    }
+}
+
+enum SubmitFutureState {
+    Failed(Option<QueueSubmitError>),
+    Submitted(Arc<WorkDoneSignal>),
+}
+
+/// Future returned by `queue_submit_async`, resolving when the GPU
+/// is done with the submission.
+pub struct QueueSubmitFuture {
+    state: SubmitFutureState,
+}
+
+impl Future for QueueSubmitFuture {
+    type Output = Result<(), QueueSubmitError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.get_mut().state {
+            SubmitFutureState::Failed(ref mut error) => {
+                Poll::Ready(Err(error.take().expect("polled after completion")))
+            }
+            SubmitFutureState::Submitted(ref signal) => {
+                if signal.poll(cx.waker()) {
+                    Poll::Ready(Ok(()))
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
 }
 
 //TODO: move out common parts of write_xxx.
@@ -313,6 +1613,143 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         buffer_id: id::BufferId,
         buffer_offset: wgt::BufferAddress,
         data: &[u8],
+    ) -> Result<(), QueueWriteError> {
+        self.queue_write_buffer_impl::<A>(queue_id, buffer_id, None, buffer_offset, data, None)
+    }
+
+    /// Like `queue_write_buffer`, but first checks that `buffer_id` still
+    /// refers to the same buffer.
+    ///
+    /// The epoch stored in the slot of `buffer_id` is compared against
+    /// `expected_epoch`, or against the epoch of `buffer_id` itself if `None`.
+    /// If the slot has been recycled in the meantime, `QueueWriteError::StaleBuffer`
+    /// is returned and nothing is written. Otherwise the buffer currently in
+    /// the slot is written, whatever epoch `buffer_id` carries.
+    pub fn queue_write_buffer_checked<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        expected_epoch: Option<Epoch>,
+        buffer_offset: wgt::BufferAddress,
+        data: &[u8],
+    ) -> Result<(), QueueWriteError> {
+        let (_, id_epoch, _) = buffer_id.unzip();
+        let expected_epoch = expected_epoch.unwrap_or(id_epoch);
+        self.queue_write_buffer_impl::<A>(
+            queue_id,
+            buffer_id,
+            Some(expected_epoch),
+            buffer_offset,
+            data,
+            None,
+        )
+    }
+
+    /// Like `queue_write_buffer`, but first checks that `data` is exactly
+    /// `expected_len` bytes long, if given.
+    ///
+    /// This catches accidental short writes, such as updating only the head of
+    /// a uniform struct and leaving stale data in its tail. On a mismatch,
+    /// `QueueWriteError::UnexpectedDataLength` is returned and nothing is written.
+    pub fn queue_write_buffer_exact<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        buffer_offset: wgt::BufferAddress,
+        data: &[u8],
+        expected_len: Option<wgt::BufferAddress>,
+    ) -> Result<(), QueueWriteError> {
+        match expected_len {
+            Some(expected) if data.len() as wgt::BufferAddress != expected => {
+                Err(QueueWriteError::UnexpectedDataLength {
+                    expected,
+                    actual: data.len() as wgt::BufferAddress,
+                })
+            }
+            _ => self.queue_write_buffer_impl::<A>(
+                queue_id,
+                buffer_id,
+                None,
+                buffer_offset,
+                data,
+                None,
+            ),
+        }
+    }
+
+    /// Like `queue_write_buffer`, but leaves the buffer ready for `next_usage`
+    /// once written, instead of in the copy destination state.
+    ///
+    /// This saves a barrier in front of the next command buffer that uses
+    /// the buffer this way. `next_usage` has to be allowed by the usage of the
+    /// buffer, and be either a set of read-only usages or a single writable one.
+    /// Mapping usages are not accepted, since mapping takes care of them.
+    pub fn queue_write_buffer_hinted<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        buffer_offset: wgt::BufferAddress,
+        data: &[u8],
+        next_usage: hal::BufferUses,
+    ) -> Result<(), QueueWriteError> {
+        self.queue_write_buffer_impl::<A>(
+            queue_id,
+            buffer_id,
+            None,
+            buffer_offset,
+            data,
+            Some(next_usage),
+        )
+    }
+
+    /// Write `data` into whichever of two interchangeable `buffers` isn't
+    /// used by work still running on the GPU, and return its id.
+    ///
+    /// This double-buffering avoids having the write wait for the work that
+    /// reads the other buffer. If both are in use, the one whose last
+    /// submission comes first is written, as it is the first to be free.
+    pub fn queue_write_buffer_double_buffered<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffers: [id::BufferId; 2],
+        buffer_offset: wgt::BufferAddress,
+        data: &[u8],
+    ) -> Result<id::BufferId, QueueWriteError> {
+        let buffer_id = {
+            let hub = A::hub(self);
+            let mut token = Token::root();
+            let (device_guard, mut token) = hub.devices.read(&mut token);
+            let device = device_guard
+                .get(queue_id)
+                .map_err(|_| DeviceError::Invalid)?;
+            let (buffer_guard, _) = hub.buffers.read(&mut token);
+
+            let mut last_uses = [None; 2];
+            for (last_use, &id) in last_uses.iter_mut().zip(buffers.iter()) {
+                let buffer = buffer_guard
+                    .get(id)
+                    .map_err(|_| TransferError::InvalidBuffer(id))?;
+                *last_use = device.in_flight_use(&buffer.life_guard)?;
+            }
+            match last_uses {
+                [Some(first), Some(second)] if second < first => buffers[1],
+                [Some(_), None] => buffers[1],
+                _ => buffers[0],
+            }
+        };
+
+        self.queue_write_buffer_impl::<A>(queue_id, buffer_id, None, buffer_offset, data, None)?;
+        Ok(buffer_id)
+    }
+
+    fn queue_write_buffer_impl<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        expected_epoch: Option<Epoch>,
+        buffer_offset: wgt::BufferAddress,
+        data: &[u8],
+        next_usage: Option<hal::BufferUses>,
     ) -> Result<(), QueueWriteError> {
         profiling::scope!("write_buffer", "Queue");
 
@@ -324,6 +1761,13 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             .map_err(|_| DeviceError::Invalid)?;
         let (buffer_guard, _) = hub.buffers.read(&mut token);
 
+        let buffer_id = match expected_epoch {
+            Some(expected) => {
+                check_buffer_epoch(buffer_id, expected, buffer_guard.current_epoch(buffer_id))?
+            }
+            None => buffer_id,
+        };
+
         #[cfg(feature = "trace")]
         if let Some(ref trace) = device.trace {
             let mut trace = trace.lock();
@@ -331,7 +1775,8 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             trace.add(Action::WriteBuffer {
                 id: buffer_id,
                 data: data_path,
-                range: buffer_offset..buffer_offset + data.len() as wgt::BufferAddress,
+                range: buffer_offset
+                    ..buffer_offset.saturating_add(data.len() as wgt::BufferAddress),
                 queued: true,
             });
         }
@@ -342,8 +1787,14 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             return Ok(());
         }
 
-        let stage = device.prepare_stage(data_size)?;
-        unsafe { stage.write(&device.raw, 0, data) }.map_err(DeviceError::from)?;
+        // Zeros don't need to go through a staging buffer.
+        let stage = if is_zero_fill(data, device.zero_write_fill_threshold) {
+            None
+        } else {
+            let stage = device.prepare_stage(data_size)?;
+            unsafe { stage.write(&device.raw, &mut device.staging_map_stats, 0, data) }?;
+            Some(stage)
+        };
 
         let mut trackers = device.trackers.lock();
         let (dst, transition) = trackers
@@ -354,25 +1805,37 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             .raw
             .as_ref()
             .ok_or(TransferError::InvalidBuffer(buffer_id))?;
-        if !dst.usage.contains(wgt::BufferUsages::COPY_DST) {
-            return Err(TransferError::MissingCopyDstUsageFlag(Some(buffer_id), None).into());
+        validate_buffer_write(
+            dst,
+            buffer_id,
+            buffer_offset,
+            data_size,
+            &device.copy_limits,
+        )?;
+        if device.write_after_read != WriteAfterRead::Serialize {
+            if let Some(submission_index) = device.in_flight_use(&dst.life_guard)? {
+                if device.write_after_read == WriteAfterRead::Error {
+                    return Err(QueueWriteError::WriteAfterRead {
+                        buffer_id,
+                        submission_index,
+                    });
+                }
+                log::warn!(
+                    "Buffer {:?} is written while submission {} may still use it, \
+                    so the write waits for that work on the GPU",
+                    buffer_id,
+                    submission_index
+                );
+            }
         }
         dst.life_guard.use_at(device.active_submission_index + 1);
 
-        if data_size % wgt::COPY_BUFFER_ALIGNMENT != 0 {
-            return Err(TransferError::UnalignedCopySize(data_size).into());
-        }
-        if buffer_offset % wgt::COPY_BUFFER_ALIGNMENT != 0 {
-            return Err(TransferError::UnalignedBufferOffset(buffer_offset).into());
-        }
-        if buffer_offset + data_size > dst.size {
-            return Err(TransferError::BufferOverrun {
-                start_offset: buffer_offset,
-                end_offset: buffer_offset + data_size,
-                buffer_size: dst.size,
-                side: CopySide::Destination,
+        if let Some(usage) = next_usage {
+            let allowed = conv::map_buffer_usage(dst.usage)
+                - (hal::BufferUses::MAP_READ | hal::BufferUses::MAP_WRITE);
+            if !is_valid_buffer_state(usage, allowed) {
+                return Err(QueueWriteError::InvalidNextUsage(usage));
             }
-            .into());
         }
 
         let region = wgt::BufferSize::new(data.len() as u64).map(|size| hal::BufferCopy {
@@ -380,18 +1843,65 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             dst_offset: buffer_offset,
             size,
         });
-        let barriers = iter::once(hal::BufferBarrier {
-            buffer: &stage.buffer,
-            usage: hal::BufferUses::MAP_WRITE..hal::BufferUses::COPY_SRC,
-        })
-        .chain(transition.map(|pending| pending.into_hal(dst)));
+        // A transition of the destination orders the previous writes already.
+        // Otherwise, copies into overlapping ranges need a barrier between
+        // them, so that the last write wins.
+        let dst_barrier = transition.map(|pending| pending.into_hal(dst)).next();
+        if dst_barrier.is_some() {
+            device.pending_writes.track_buffer_barrier(buffer_id);
+        }
+        let overlap_barrier = if device
+            .pending_writes
+            .track_buffer_write(buffer_id, buffer_offset..buffer_offset + data_size)
+        {
+            Some(hal::BufferBarrier {
+                buffer: dst_raw,
+                usage: hal::BufferUses::COPY_DST..hal::BufferUses::COPY_DST,
+            })
+        } else {
+            None
+        };
+        let barriers = stage
+            .as_ref()
+            .map(|stage| hal::BufferBarrier {
+                buffer: &stage.buffer,
+                usage: hal::BufferUses::MAP_WRITE..hal::BufferUses::COPY_SRC,
+            })
+            .into_iter()
+            .chain(dst_barrier)
+            .chain(overlap_barrier);
+        let marker = copy_debug_marker(device.copy_debug_markers, || {
+            format!("write_buffer -> {:?}, {} bytes", buffer_id, data_size)
+        });
         let encoder = device.pending_writes.activate();
         unsafe {
+            if let Some(ref marker) = marker {
+                encoder.begin_debug_marker(marker);
+            }
             encoder.transition_buffers(barriers);
-            encoder.copy_buffer_to_buffer(&stage.buffer, dst_raw, region.into_iter());
+            match stage {
+                Some(ref stage) => {
+                    encoder.copy_buffer_to_buffer(&stage.buffer, dst_raw, region.into_iter())
+                }
+                None => encoder.fill_buffer(dst_raw, buffer_offset..buffer_offset + data_size, 0),
+            }
+            if marker.is_some() {
+                encoder.end_debug_marker();
+            }
+        }
+        if let Some(usage) = next_usage {
+            let (dst, transition) = trackers
+                .buffers
+                .use_replace(&*buffer_guard, buffer_id, (), usage)
+                .map_err(TransferError::InvalidBuffer)?;
+            unsafe {
+                encoder.transition_buffers(transition.map(|pending| pending.into_hal(dst)));
+            }
         }
 
-        device.pending_writes.consume(stage);
+        if let Some(stage) = stage {
+            device.pending_writes.consume(stage);
+        }
         device.pending_writes.dst_buffers.insert(buffer_id);
 
         // Ensure the overwritten bytes are marked as initialized so they don't need to be nulled prior to mapping or binding.
@@ -407,15 +1917,18 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         Ok(())
     }
 
-    pub fn queue_write_texture<A: HalApi>(
+    /// Make sure `range` of the buffer is initialized with zeros on the GPU
+    /// before any work submitted afterwards, even if no submitted command uses it.
+    ///
+    /// This is useful for buffers that are about to be mapped for reading
+    /// without ever having been written to.
+    pub fn queue_ensure_buffer_initialized<A: HalApi>(
         &self,
         queue_id: id::QueueId,
-        destination: &ImageCopyTexture,
-        data: &[u8],
-        data_layout: &wgt::ImageDataLayout,
-        size: &wgt::Extent3d,
+        buffer_id: id::BufferId,
+        range: Range<wgt::BufferAddress>,
     ) -> Result<(), QueueWriteError> {
-        profiling::scope!("write_texture", "Queue");
+        profiling::scope!("ensure_buffer_initialized", "Queue");
 
         let hub = A::hub(self);
         let mut token = Token::root();
@@ -423,66 +1936,494 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         let device = device_guard
             .get_mut(queue_id)
             .map_err(|_| DeviceError::Invalid)?;
+        let (mut buffer_guard, _) = hub.buffers.write(&mut token);
 
-        #[cfg(feature = "trace")]
-        if let Some(ref trace) = device.trace {
-            let mut trace = trace.lock();
-            let data_path = trace.make_binary("bin", data);
-            trace.add(Action::WriteTexture {
-                to: destination.clone(),
-                data: data_path,
-                layout: *data_layout,
-                size: *size,
-            });
-        }
-
-        if size.width == 0 || size.height == 0 || size.depth_or_array_layers == 0 {
-            log::trace!("Ignoring write_texture of size 0");
-            return Ok(());
+        if range.start % wgt::COPY_BUFFER_ALIGNMENT != 0 {
+            return Err(TransferError::UnalignedBufferOffset(range.start).into());
         }
-
-        let (texture_guard, _) = hub.textures.read(&mut token);
-        let (selector, dst_base, texture_format) =
-            extract_texture_selector(destination, size, &*texture_guard)?;
-        let format_desc = texture_format.describe();
-        let (_, bytes_per_array_layer) = validate_linear_texture_data(
-            data_layout,
-            texture_format,
-            data.len() as wgt::BufferAddress,
-            CopySide::Source,
-            format_desc.block_size as wgt::BufferAddress,
-            size,
-            false,
-        )?;
-
-        if !conv::is_valid_copy_dst_texture_format(texture_format) {
-            return Err(TransferError::CopyToForbiddenTextureFormat(texture_format).into());
+        if range.end < range.start || range.end % wgt::COPY_BUFFER_ALIGNMENT != 0 {
+            return Err(
+                TransferError::UnalignedCopySize(range.end.wrapping_sub(range.start)).into(),
+            );
         }
-        let (block_width, block_height) = format_desc.block_dimensions;
-        let width_blocks = size.width / block_width as u32;
-        let height_blocks = size.height / block_height as u32;
 
-        let block_rows_per_image = match data_layout.rows_per_image {
-            Some(rows_per_image) => rows_per_image.get(),
-            None => {
-                // doesn't really matter because we need this only if we copy more than one layer, and then we validate for this being not None
-                size.height
+        let uninitialized_ranges = {
+            let buffer = buffer_guard
+                .get_mut(buffer_id)
+                .map_err(|_| TransferError::InvalidBuffer(buffer_id))?;
+            if buffer.raw.is_none() {
+                return Err(TransferError::InvalidBuffer(buffer_id).into());
+            }
+            if range.end > buffer.size {
+                return Err(TransferError::BufferOverrun {
+                    start_offset: range.start,
+                    end_offset: range.end,
+                    buffer_size: buffer.size,
+                    side: CopySide::Destination,
+                }
+                .into());
             }
+            buffer
+                .initialization_status
+                .drain(range)
+                .collect::<Vec<_>>()
         };
+        device.fill_buffer_ranges_with_zeros(&*buffer_guard, buffer_id, uninitialized_ranges)?;
+        Ok(())
+    }
 
-        let bytes_per_row_alignment = get_lowest_common_denom(
-            device.alignments.buffer_copy_pitch.get() as u32,
-            format_desc.block_size as u32,
-        );
-        let stage_bytes_per_row = align_to(
-            format_desc.block_size as u32 * width_blocks,
-            bytes_per_row_alignment,
-        );
-
-        let block_rows_in_copy =
-            (size.depth_or_array_layers - 1) * block_rows_per_image + height_blocks;
+    /// Make sure all of the buffer is initialized on the GPU before any work
+    /// submitted afterwards, by filling the ranges that were never written to
+    /// with zeros.
+    ///
+    /// This is the same as `queue_ensure_buffer_initialized` over the whole
+    /// buffer, and is useful before reading back a buffer that was only
+    /// partially written.
+    pub fn queue_initialize_uninitialized<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+    ) -> Result<(), QueueWriteError> {
+        profiling::scope!("initialize_uninitialized", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, mut token) = hub.devices.write(&mut token);
+        let device = device_guard
+            .get_mut(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let (mut buffer_guard, _) = hub.buffers.write(&mut token);
+
+        let uninitialized_ranges = {
+            let buffer = buffer_guard
+                .get_mut(buffer_id)
+                .map_err(|_| TransferError::InvalidBuffer(buffer_id))?;
+            if buffer.raw.is_none() {
+                return Err(TransferError::InvalidBuffer(buffer_id).into());
+            }
+            let size = buffer.size;
+            buffer
+                .initialization_status
+                .drain(0..size)
+                .map(|range| align_init_range(range, size))
+                .collect::<Vec<_>>()
+        };
+        device.fill_buffer_ranges_with_zeros(&*buffer_guard, buffer_id, uninitialized_ranges)?;
+        Ok(())
+    }
+
+    /// Set how the ranges of the buffer that were never written to are
+    /// initialized before the buffer is used on the GPU.
+    ///
+    /// With `BufferInitPolicy::Skip`, submissions don't fill anything in the
+    /// buffer with zeros, so reading a range of it that wasn't written to is
+    /// undefined behavior. This is meant for scratch buffers that are always
+    /// fully written before being read.
+    pub fn buffer_set_init_policy<A: HalApi>(
+        &self,
+        buffer_id: id::BufferId,
+        policy: BufferInitPolicy,
+    ) -> Result<(), BufferAccessError> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut buffer_guard, _) = hub.buffers.write(&mut token);
+        let buffer = buffer_guard
+            .get_mut(buffer_id)
+            .map_err(|_| BufferAccessError::Invalid)?;
+        buffer.init_policy = policy;
+        Ok(())
+    }
+
+    /// Record a barrier that leaves the whole buffer in `usage`, in front of
+    /// the next submission.
+    ///
+    /// This is meant for handing the buffer over to another API, which
+    /// expects it in a particular state. `usage` has to be allowed by the
+    /// usage of the buffer, and be either a set of read-only usages or a
+    /// single writable one.
+    pub fn queue_transition_buffer<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        usage: hal::BufferUses,
+    ) -> Result<(), QueueTransitionError> {
+        profiling::scope!("transition_buffer", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, mut token) = hub.devices.write(&mut token);
+        let device = device_guard
+            .get_mut(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let (buffer_guard, _) = hub.buffers.read(&mut token);
+
+        let buffer = buffer_guard
+            .get(buffer_id)
+            .map_err(|_| QueueTransitionError::InvalidBuffer(buffer_id))?;
+        if buffer.raw.is_none() {
+            return Err(QueueTransitionError::InvalidBuffer(buffer_id));
+        }
+        let allowed = conv::map_buffer_usage(buffer.usage)
+            - (hal::BufferUses::MAP_READ | hal::BufferUses::MAP_WRITE);
+        if !is_valid_buffer_state(usage, allowed) {
+            return Err(QueueTransitionError::InvalidBufferUsage(usage));
+        }
+
+        let mut trackers = device.trackers.lock();
+        let (buffer, transition) = trackers
+            .buffers
+            .use_replace(&*buffer_guard, buffer_id, (), usage)
+            .map_err(|_| QueueTransitionError::InvalidBuffer(buffer_id))?;
+        buffer.life_guard.use_at(device.active_submission_index + 1);
+        let encoder = device.pending_writes.activate();
+        unsafe {
+            encoder.transition_buffers(transition.map(|pending| pending.into_hal(buffer)));
+        }
+        device.pending_writes.dst_buffers.insert(buffer_id);
+
+        Ok(())
+    }
+
+    /// Record a barrier that leaves all the subresources of the texture in
+    /// `usage`, in front of the next submission.
+    ///
+    /// This is meant for handing the texture over to another API, which
+    /// expects it in a particular layout. `usage` has to be allowed by the
+    /// usage of the texture, and be either a set of read-only usages or a
+    /// single writable one.
+    pub fn queue_transition_texture<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        texture_id: id::TextureId,
+        usage: hal::TextureUses,
+    ) -> Result<(), QueueTransitionError> {
+        profiling::scope!("transition_texture", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, mut token) = hub.devices.write(&mut token);
+        let device = device_guard
+            .get_mut(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let (_, mut token) = hub.buffers.read(&mut token);
+        let (texture_guard, _) = hub.textures.read(&mut token);
+
+        let texture = texture_guard
+            .get(texture_id)
+            .map_err(|_| QueueTransitionError::InvalidTexture(texture_id))?;
+        if texture.raw.is_none() {
+            return Err(QueueTransitionError::InvalidTexture(texture_id));
+        }
+        if !is_valid_texture_state(usage, texture.hal_usage) {
+            return Err(QueueTransitionError::InvalidTextureUsage(usage));
+        }
+        let selector = texture.full_range.clone();
+
+        let mut trackers = device.trackers.lock();
+        let (texture, transition) = trackers
+            .textures
+            .use_replace(&*texture_guard, texture_id, selector, usage)
+            .map_err(|_| QueueTransitionError::InvalidTexture(texture_id))?;
+        texture
+            .life_guard
+            .use_at(device.active_submission_index + 1);
+        let encoder = device.pending_writes.activate();
+        unsafe {
+            encoder.transition_textures(transition.map(|pending| pending.into_hal(texture)));
+        }
+        device.pending_writes.dst_textures.insert(texture_id);
+
+        Ok(())
+    }
+
+    /// Write `data` into the `destination` texture, through a staging buffer.
+    ///
+    /// Swap chain images are never the destination of a write: they are only
+    /// exposed as texture views, which can be nothing but render pass
+    /// attachments, so there is no texture id to refer to them with.
+    pub fn queue_write_texture<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<(), QueueWriteError> {
+        self.queue_write_texture_chunked::<A>(queue_id, destination, &[data], data_layout, size)
+    }
+
+    /// Like `queue_write_texture`, but with the region of the texture described
+    /// in pixels, for formats whose texels are blocks of several pixels.
+    ///
+    /// The origin and size have to be multiples of the block dimensions, which
+    /// is reported with the dimensions of the blocks otherwise. A size reaching
+    /// the edge of the mip level doesn't have to: it's rounded up to the whole
+    /// blocks that `queue_write_texture` takes. The layout of `data` is the same.
+    pub fn queue_write_texture_pixels<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<(), QueueWriteError> {
+        let copy_size = {
+            let hub = A::hub(self);
+            let mut token = Token::root();
+            let (device_guard, mut token) = hub.devices.read(&mut token);
+            device_guard
+                .get(queue_id)
+                .map_err(|_| DeviceError::Invalid)?;
+            let (texture_guard, _) = hub.textures.read(&mut token);
+            let desc = &texture_guard
+                .get(destination.texture)
+                .map_err(|_| TransferError::InvalidTexture(destination.texture))?
+                .desc;
+            match desc.mip_level_size(destination.mip_level) {
+                Some(mip_size) => {
+                    pixel_region_to_copy_extent(desc.format, mip_size, destination.origin, *size)?
+                }
+                // Let the write report the invalid mip level.
+                None => *size,
+            }
+        };
+
+        self.queue_write_texture::<A>(queue_id, destination, data, data_layout, &copy_size)
+    }
+
+    /// Like `queue_write_texture`, but with the source data split across
+    /// several slices, as if they were concatenated in order.
+    ///
+    /// Chunks don't have to line up with rows or images.
+    pub fn queue_write_texture_chunked<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        chunks: &[&[u8]],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<(), QueueWriteError> {
+        self.write_texture_impl::<A>(
+            queue_id,
+            destination,
+            chunks,
+            data_layout,
+            size,
+            None,
+            false,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `queue_write_texture`, but also returns how much the rows
+    /// had to be padded for staging the data.
+    pub fn queue_write_texture_stats<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<TextureUploadStats, QueueWriteError> {
+        self.write_texture_impl::<A>(
+            queue_id,
+            destination,
+            &[data],
+            data_layout,
+            size,
+            None,
+            false,
+        )
+    }
+
+    /// Like `queue_write_texture`, but also returns the staging memory taken
+    /// by the write, next to the bytes of data it copies.
+    ///
+    /// The difference between them is the padding of the rows to the staging
+    /// pitch, and the spare capacity of a reused staging buffer.
+    pub fn queue_write_texture_accounted<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<WriteAccounting, QueueWriteError> {
+        self.write_texture_impl::<A>(
+            queue_id,
+            destination,
+            &[data],
+            data_layout,
+            size,
+            None,
+            false,
+        )
+        .map(WriteAccounting::from)
+    }
+
+    /// Like `queue_write_texture`, but with the source rows already padded to
+    /// the pitch the data is staged with, as returned by
+    /// `texture_bytes_per_row_alignment`.
+    ///
+    /// The data is staged with a single copy, without any repacking. The bytes
+    /// per row of `data_layout` have to be the staging row pitch, and the data
+    /// past the layout offset has to be exactly as large as the stage, padding
+    /// of the last row included.
+    pub fn queue_write_texture_prepadded<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+    ) -> Result<(), QueueWriteError> {
+        self.write_texture_impl::<A>(
+            queue_id,
+            destination,
+            &[data],
+            data_layout,
+            size,
+            None,
+            true,
+        )
+        .map(|_| ())
+    }
+
+    /// Like `queue_write_texture`, but with the data staged with the given
+    /// layout, as recorded by a trace, instead of the one the device picks.
+    ///
+    /// The layout has to be usable for the copies into the texture.
+    #[cfg(feature = "replay")]
+    pub fn queue_write_texture_staged<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        data: &[u8],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+        staging: &StagingLayout,
+    ) -> Result<(), QueueWriteError> {
+        self.write_texture_impl::<A>(
+            queue_id,
+            destination,
+            &[data],
+            data_layout,
+            size,
+            Some(staging),
+            false,
+        )
+        .map(|_| ())
+    }
+
+    fn write_texture_impl<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        destination: &ImageCopyTexture,
+        chunks: &[&[u8]],
+        data_layout: &wgt::ImageDataLayout,
+        size: &wgt::Extent3d,
+        staging_layout: Option<&StagingLayout>,
+        prepadded: bool,
+    ) -> Result<TextureUploadStats, QueueWriteError> {
+        profiling::scope!("write_texture", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, mut token) = hub.devices.write(&mut token);
+        let device = device_guard
+            .get_mut(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let mut data = ChunkedSource::new(chunks);
+        let (texture_guard, _) = hub.textures.read(&mut token);
+
+        #[cfg(feature = "trace")]
+        if let Some(ref trace) = device.trace {
+            let mut trace = trace.lock();
+            let data_path = match *chunks {
+                [data] => trace.make_binary("bin", data),
+                _ => trace.make_binary("bin", &chunks.concat()),
+            };
+            let staging = match staging_layout {
+                Some(layout) => Some(layout.clone()),
+                None => texture_guard.get(destination.texture).ok().map(|texture| {
+                    device
+                        .texture_staging(texture.desc.format, data_layout, size)
+                        .layout()
+                }),
+            };
+            trace.add(Action::WriteTexture {
+                to: destination.clone(),
+                data: data_path,
+                layout: *data_layout,
+                size: *size,
+                staging,
+            });
+        }
+
+        if size.width == 0 || size.height == 0 || size.depth_or_array_layers == 0 {
+            log::trace!("Ignoring write_texture of size 0");
+            return Ok(TextureUploadStats::default());
+        }
+
+        let TextureWriteTarget {
+            selector,
+            base: dst_base,
+            format: texture_format,
+            copy_size: hal_copy_size,
+            array_layer_count,
+        } = validate_texture_write(
+            destination,
+            data.len() as wgt::BufferAddress,
+            data_layout,
+            size,
+            &*texture_guard,
+        )?;
+        let format_desc = texture_format.describe();
+        let TextureStaging {
+            bytes_per_row,
+            bytes_in_row,
+            stage_bytes_per_row,
+            height_blocks,
+            block_rows_per_image,
+            gpu_repack,
+        } = match staging_layout {
+            Some(layout) => device
+                .texture_staging(texture_format, data_layout, size)
+                .with_layout(layout, device.bytes_per_row_alignment(texture_format))?,
+            None => device.texture_staging(texture_format, data_layout, size),
+        };
+
+        let block_rows_in_copy =
+            (size.depth_or_array_layers - 1) * block_rows_per_image + height_blocks;
         let stage_size = stage_bytes_per_row as u64 * block_rows_in_copy as u64;
-        let stage = device.prepare_stage(stage_size)?;
+
+        if prepadded {
+            if bytes_per_row != stage_bytes_per_row {
+                return Err(QueueWriteError::MismatchedRowPadding {
+                    bytes_per_row,
+                    stage_bytes_per_row,
+                });
+            }
+            let data_size = data.len() as wgt::BufferAddress - data_layout.offset;
+            if data_size != stage_size {
+                return Err(QueueWriteError::UnexpectedDataLength {
+                    expected: stage_size,
+                    actual: data_size,
+                });
+            }
+        }
+
+        let (upload, stage) = if gpu_repack {
+            // Upload the rows with the source pitch, up to the end of the last one.
+            let upload_size = (block_rows_in_copy - 1) as wgt::BufferAddress
+                * bytes_per_row as wgt::BufferAddress
+                + bytes_in_row as wgt::BufferAddress;
+            let upload = device.prepare_stage(upload_size)?;
+            (upload, Some(device.prepare_repack_stage(stage_size)?))
+        } else {
+            (device.prepare_stage(stage_size)?, None)
+        };
+        let staging_capacity = upload.capacity + stage.as_ref().map_or(0, |stage| stage.capacity);
 
         let mut trackers = device.trackers.lock();
         let (dst, transition) = trackers
@@ -498,90 +2439,496 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
             .raw
             .as_ref()
             .ok_or(TransferError::InvalidTexture(destination.texture))?;
-
-        if !dst.desc.usage.contains(wgt::TextureUsages::COPY_DST) {
-            return Err(
-                TransferError::MissingCopyDstUsageFlag(None, Some(destination.texture)).into(),
-            );
+        if log::log_enabled!(log::Level::Info) {
+            if let Some(last_use) = device.in_flight_use(&dst.life_guard)? {
+                log::info!(
+                    "Texture {:?} is written while submission {} may still use it, \
+                    so the write waits for that work on the GPU",
+                    destination.texture,
+                    last_use
+                );
+            }
         }
-        let (hal_copy_size, array_layer_count) =
-            validate_texture_copy_range(destination, &dst.desc, CopySide::Destination, size)?;
         dst.life_guard.use_at(device.active_submission_index + 1);
 
-        let bytes_per_row = if let Some(bytes_per_row) = data_layout.bytes_per_row {
-            bytes_per_row.get()
-        } else {
-            width_blocks * format_desc.block_size as u32
-        };
-
-        let mapping = unsafe { device.raw.map_buffer(&stage.buffer, 0..stage_size) }
+        // The source data starts at the layout offset. Its last row may be
+        // shorter than `bytes_per_row`, so it can be smaller than the stage.
+        data.skip(data_layout.offset as usize);
+        // The staging buffer is mapped once, and stays mapped across all the chunks.
+        let raw = &device.raw;
+        let stats = &mut device.staging_map_stats;
+        let mapping = stats
+            .map(|| unsafe { raw.map_buffer(&upload.buffer, 0..upload.size) })
             .map_err(DeviceError::from)?;
         unsafe {
             profiling::scope!("copy");
-            if stage_bytes_per_row == bytes_per_row {
-                // Fast path if the data isalready being aligned optimally.
-                ptr::copy_nonoverlapping(data.as_ptr(), mapping.ptr.as_ptr(), stage_size as usize);
+            let upload_data = mapped_slice(&mapping, upload.size, 0, upload.size as usize);
+            if gpu_repack || stage_bytes_per_row == bytes_per_row {
+                // Fast path if the data is already being aligned optimally,
+                // or if the GPU is going to take care of the alignment.
+                let copy_size = upload_data.len().min(data.len());
+                data.read(0, &mut upload_data[..copy_size]);
+                if !mapping.is_coherent {
+                    stats.flush(|| {
+                        raw.flush_mapped_ranges(&upload.buffer, iter::once(0..upload.size))
+                    });
+                }
             } else {
-                // Copy row by row into the optimal alignment.
-                let copy_bytes_per_row = stage_bytes_per_row.min(bytes_per_row) as usize;
-                for layer in 0..size.depth_or_array_layers {
-                    let rows_offset = layer * block_rows_per_image;
-                    for row in 0..height_blocks {
-                        ptr::copy_nonoverlapping(
-                            data.as_ptr()
-                                .offset((rows_offset + row) as isize * bytes_per_row as isize),
-                            mapping.ptr.as_ptr().offset(
-                                (rows_offset + row) as isize * stage_bytes_per_row as isize,
-                            ),
-                            copy_bytes_per_row,
-                        );
+                // Copy row by row into the optimal alignment, flushing each chunk.
+                let repack = RowRepack {
+                    src_bytes_per_row: bytes_per_row,
+                    dst_bytes_per_row: stage_bytes_per_row,
+                    bytes_in_row,
+                    height_blocks,
+                    block_rows_per_image,
+                    layers: size.depth_or_array_layers,
+                };
+                repack.copy_chunked(&mut data, upload_data, REPACK_CHUNK_ROWS, |range| {
+                    if !mapping.is_coherent {
+                        stats.flush(|| {
+                            raw.flush_mapped_ranges(
+                                &upload.buffer,
+                                iter::once(range.start as u64..range.end as u64),
+                            )
+                        });
                     }
-                }
+                });
             }
         }
-        unsafe {
-            if !mapping.is_coherent {
-                device
-                    .raw
-                    .flush_mapped_ranges(&stage.buffer, iter::once(0..stage_size));
-            }
-            device
-                .raw
-                .unmap_buffer(&stage.buffer)
-                .map_err(DeviceError::from)?;
-        }
+        stats
+            .unmap(|| unsafe { raw.unmap_buffer(&upload.buffer) })
+            .map_err(DeviceError::from)?;
 
-        let regions = (0..array_layer_count).map(|rel_array_layer| {
+        // Layers are laid out in the stage with its own row pitch, padding rows included.
+        let bytes_per_stage_layer = stage_bytes_per_row as u64 * block_rows_per_image as u64;
+        let max_copy_extent = device.copy_limits.max_texture_copy_extent;
+        let regions = (0..array_layer_count).flat_map(|rel_array_layer| {
             let mut texture_base = dst_base.clone();
             texture_base.array_layer += rel_array_layer;
-            hal::BufferTextureCopy {
+            let region = hal::BufferTextureCopy {
                 buffer_layout: wgt::ImageDataLayout {
-                    offset: rel_array_layer as u64 * bytes_per_array_layer,
+                    offset: rel_array_layer as u64 * bytes_per_stage_layer,
                     bytes_per_row: NonZeroU32::new(stage_bytes_per_row),
                     rows_per_image: NonZeroU32::new(block_rows_per_image),
                 },
                 texture_base,
                 size: hal_copy_size,
-            }
+            };
+            split_texture_copy(region, &format_desc, &max_copy_extent)
         });
         let barrier = hal::BufferBarrier {
-            buffer: &stage.buffer,
+            buffer: &upload.buffer,
             usage: hal::BufferUses::MAP_WRITE..hal::BufferUses::COPY_SRC,
         };
 
+        let marker = copy_debug_marker(device.copy_debug_markers, || {
+            format!(
+                "write_texture -> {:?}, {}x{}x{}",
+                destination.texture, size.width, size.height, size.depth_or_array_layers
+            )
+        });
         let encoder = device.pending_writes.activate();
         unsafe {
+            if let Some(ref marker) = marker {
+                encoder.begin_debug_marker(marker);
+            }
             encoder.transition_buffers(iter::once(barrier));
+            if let Some(ref stage) = stage {
+                let row_copies = (0..size.depth_or_array_layers).flat_map(|layer| {
+                    let rows_offset = layer * block_rows_per_image;
+                    (rows_offset..rows_offset + height_blocks).map(|row| hal::BufferCopy {
+                        src_offset: row as wgt::BufferAddress * bytes_per_row as wgt::BufferAddress,
+                        dst_offset: row as wgt::BufferAddress
+                            * stage_bytes_per_row as wgt::BufferAddress,
+                        size: wgt::BufferSize::new(bytes_in_row as wgt::BufferAddress).unwrap(),
+                    })
+                });
+                encoder.transition_buffers(iter::once(hal::BufferBarrier {
+                    buffer: &stage.buffer,
+                    usage: hal::BufferUses::empty()..hal::BufferUses::COPY_DST,
+                }));
+                encoder.copy_buffer_to_buffer(&upload.buffer, &stage.buffer, row_copies);
+                encoder.transition_buffers(iter::once(hal::BufferBarrier {
+                    buffer: &stage.buffer,
+                    usage: hal::BufferUses::COPY_DST..hal::BufferUses::COPY_SRC,
+                }));
+            }
             encoder.transition_textures(transition.map(|pending| pending.into_hal(dst)));
-            encoder.copy_buffer_to_texture(&stage.buffer, dst_raw, regions);
+            let src_raw = &stage.as_ref().unwrap_or(&upload).buffer;
+            encoder.copy_buffer_to_texture(src_raw, dst_raw, regions);
+            if marker.is_some() {
+                encoder.end_debug_marker();
+            }
         }
 
-        device.pending_writes.consume(stage);
+        device.pending_writes.consume(upload);
+        if let Some(stage) = stage {
+            // Repack buffers aren't mappable, and don't go to the staging pool.
+            device
+                .pending_writes
+                .consume_temp(TempResource::Buffer(stage.buffer));
+        }
         device
             .pending_writes
             .dst_textures
             .insert(destination.texture);
 
+        Ok(TextureUploadStats {
+            staging_capacity,
+            ..TextureUploadStats::new(
+                bytes_in_row as u64 * height_blocks as u64 * size.depth_or_array_layers as u64,
+                stage_size,
+            )
+        })
+    }
+
+    /// Perform several buffer and texture writes at once, sharing a single
+    /// staging buffer that is mapped only once for all of them.
+    ///
+    /// Every write is validated before anything is staged, so either all of
+    /// them are recorded, or none is.
+    pub fn queue_write_batch<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        ops: &[WriteOp],
+    ) -> Result<(), QueueWriteError> {
+        profiling::scope!("write_batch", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, mut token) = hub.devices.write(&mut token);
+        let device = device_guard
+            .get_mut(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        let (mut buffer_guard, mut token) = hub.buffers.write(&mut token);
+        let (texture_guard, _) = hub.textures.read(&mut token);
+
+        #[cfg(feature = "trace")]
+        if let Some(ref trace) = device.trace {
+            let mut trace = trace.lock();
+            for op in ops.iter() {
+                let action = match *op {
+                    WriteOp::Buffer {
+                        buffer_id,
+                        offset,
+                        data,
+                    } => Action::WriteBuffer {
+                        id: buffer_id,
+                        data: trace.make_binary("bin", data),
+                        range: offset..offset.saturating_add(data.len() as wgt::BufferAddress),
+                        queued: true,
+                    },
+                    WriteOp::Texture {
+                        ref destination,
+                        data,
+                        layout,
+                        size,
+                    } => Action::WriteTexture {
+                        to: destination.clone(),
+                        data: trace.make_binary("bin", data),
+                        layout,
+                        size,
+                        staging: texture_guard.get(destination.texture).ok().map(|texture| {
+                            device
+                                .batch_texture_staging(texture.desc.format, &layout, &size)
+                                .layout()
+                        }),
+                    },
+                };
+                trace.add(action);
+            }
+        }
+
+        // Validate all the writes and lay out their data in the staging buffer.
+        let mut stage_size = 0;
+        let mut staged = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            match *op {
+                WriteOp::Buffer {
+                    buffer_id,
+                    offset,
+                    data,
+                } => {
+                    let data_size = data.len() as wgt::BufferAddress;
+                    let dst = buffer_guard
+                        .get(buffer_id)
+                        .map_err(|_| TransferError::InvalidBuffer(buffer_id))?;
+                    validate_buffer_write(dst, buffer_id, offset, data_size, &device.copy_limits)?;
+                    if data_size == 0 {
+                        staged.push(None);
+                        continue;
+                    }
+
+                    let stage_offset = align_to_address(stage_size, wgt::COPY_BUFFER_ALIGNMENT);
+                    stage_size = stage_offset + data_size;
+                    staged.push(Some(StagedWrite::Buffer { stage_offset }));
+                }
+                WriteOp::Texture {
+                    ref destination,
+                    data,
+                    ref layout,
+                    ref size,
+                } => {
+                    if size.width == 0 || size.height == 0 || size.depth_or_array_layers == 0 {
+                        staged.push(None);
+                        continue;
+                    }
+
+                    let TextureWriteTarget {
+                        selector,
+                        base,
+                        format,
+                        copy_size,
+                        array_layer_count,
+                    } = validate_texture_write(
+                        destination,
+                        data.len() as wgt::BufferAddress,
+                        layout,
+                        size,
+                        &*texture_guard,
+                    )?;
+                    let format_desc = format.describe();
+
+                    let staging = device.batch_texture_staging(format, layout, size);
+                    let block_rows_in_copy = (size.depth_or_array_layers - 1)
+                        * staging.block_rows_per_image
+                        + staging.height_blocks;
+
+                    let offset_alignment = get_lowest_common_denom(
+                        get_lowest_common_denom(
+                            device.alignments.buffer_copy_offset.get() as u32,
+                            format_desc.block_size as u32,
+                        ),
+                        wgt::COPY_BUFFER_ALIGNMENT as u32,
+                    );
+                    let stage_offset = align_to_address(stage_size, offset_alignment);
+                    let texture_stage_size =
+                        staging.stage_bytes_per_row as u64 * block_rows_in_copy as u64;
+                    stage_size = stage_offset + texture_stage_size;
+                    staged.push(Some(StagedWrite::Texture {
+                        stage_offset,
+                        stage_size: texture_stage_size,
+                        selector,
+                        base,
+                        copy_size,
+                        array_layer_count,
+                        format_desc,
+                        staging,
+                    }));
+                }
+            }
+        }
+
+        if stage_size == 0 {
+            log::trace!("Ignoring write_batch without any data");
+            return Ok(());
+        }
+
+        let stage = device.prepare_stage(stage_size)?;
+        let raw = &device.raw;
+        let stats = &mut device.staging_map_stats;
+        let mapping = stats
+            .map(|| unsafe { raw.map_buffer(&stage.buffer, 0..stage_size) })
+            .map_err(DeviceError::from)?;
+        let mapping_size = stage_size;
+        unsafe {
+            profiling::scope!("copy");
+            for (op, staged) in ops.iter().zip(staged.iter()) {
+                match (op, staged) {
+                    (
+                        &WriteOp::Buffer { data, .. },
+                        &Some(StagedWrite::Buffer { stage_offset }),
+                    ) => {
+                        copy_into_mapping(&mapping, mapping_size, stage_offset, data);
+                    }
+                    (
+                        &WriteOp::Texture {
+                            data,
+                            ref layout,
+                            ref size,
+                            ..
+                        },
+                        &Some(StagedWrite::Texture {
+                            stage_offset,
+                            stage_size,
+                            ref staging,
+                            ..
+                        }),
+                    ) => {
+                        let repack = RowRepack {
+                            src_bytes_per_row: staging.bytes_per_row,
+                            dst_bytes_per_row: staging.stage_bytes_per_row,
+                            bytes_in_row: staging.bytes_in_row,
+                            height_blocks: staging.height_blocks,
+                            block_rows_per_image: staging.block_rows_per_image,
+                            layers: size.depth_or_array_layers,
+                        };
+                        repack.copy(
+                            &data[layout.offset as usize..],
+                            mapped_slice(&mapping, mapping_size, stage_offset, stage_size as usize),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !mapping.is_coherent {
+            stats.flush(|| unsafe {
+                raw.flush_mapped_ranges(&stage.buffer, iter::once(0..stage_size))
+            });
+        }
+        stats
+            .unmap(|| unsafe { raw.unmap_buffer(&stage.buffer) })
+            .map_err(DeviceError::from)?;
+
+        let mut trackers = device.trackers.lock();
+        let encoder = device.pending_writes.activate();
+        unsafe {
+            encoder.transition_buffers(iter::once(hal::BufferBarrier {
+                buffer: &stage.buffer,
+                usage: hal::BufferUses::MAP_WRITE..hal::BufferUses::COPY_SRC,
+            }));
+        }
+        for (op, staged) in ops.iter().zip(staged.into_iter()) {
+            match (op, staged) {
+                (
+                    &WriteOp::Buffer {
+                        buffer_id,
+                        offset,
+                        data,
+                    },
+                    Some(StagedWrite::Buffer { stage_offset }),
+                ) => {
+                    let data_size = data.len() as wgt::BufferAddress;
+                    let (dst, transition) = trackers
+                        .buffers
+                        .use_replace(&*buffer_guard, buffer_id, (), hal::BufferUses::COPY_DST)
+                        .map_err(TransferError::InvalidBuffer)?;
+                    let dst_raw = dst.raw.as_ref().unwrap();
+                    dst.life_guard.use_at(device.active_submission_index + 1);
+                    let dst_barrier = transition.map(|pending| pending.into_hal(dst)).next();
+                    if dst_barrier.is_some() {
+                        device.pending_writes.track_buffer_barrier(buffer_id);
+                    }
+                    let overlap_barrier = if device
+                        .pending_writes
+                        .track_buffer_write(buffer_id, offset..offset + data_size)
+                    {
+                        Some(hal::BufferBarrier {
+                            buffer: dst_raw,
+                            usage: hal::BufferUses::COPY_DST..hal::BufferUses::COPY_DST,
+                        })
+                    } else {
+                        None
+                    };
+                    let region = wgt::BufferSize::new(data_size).map(|size| hal::BufferCopy {
+                        src_offset: stage_offset,
+                        dst_offset: offset,
+                        size,
+                    });
+                    let marker = copy_debug_marker(device.copy_debug_markers, || {
+                        format!("write_batch -> {:?}, {} bytes", buffer_id, data_size)
+                    });
+                    let encoder = device.pending_writes.activate();
+                    unsafe {
+                        if let Some(ref marker) = marker {
+                            encoder.begin_debug_marker(marker);
+                        }
+                        encoder.transition_buffers(dst_barrier.into_iter().chain(overlap_barrier));
+                        encoder.copy_buffer_to_buffer(&stage.buffer, dst_raw, region.into_iter());
+                        if marker.is_some() {
+                            encoder.end_debug_marker();
+                        }
+                    }
+                    device.pending_writes.dst_buffers.insert(buffer_id);
+                }
+                (
+                    &WriteOp::Texture {
+                        ref destination, ..
+                    },
+                    Some(StagedWrite::Texture {
+                        stage_offset,
+                        selector,
+                        base,
+                        copy_size,
+                        array_layer_count,
+                        format_desc,
+                        staging,
+                        ..
+                    }),
+                ) => {
+                    let (dst, transition) = trackers
+                        .textures
+                        .use_replace(
+                            &*texture_guard,
+                            destination.texture,
+                            selector,
+                            hal::TextureUses::COPY_DST,
+                        )
+                        .unwrap();
+                    let dst_raw = dst.raw.as_ref().unwrap();
+                    dst.life_guard.use_at(device.active_submission_index + 1);
+                    let bytes_per_stage_layer =
+                        staging.stage_bytes_per_row as u64 * staging.block_rows_per_image as u64;
+                    let max_copy_extent = device.copy_limits.max_texture_copy_extent;
+                    let regions = (0..array_layer_count).flat_map(|rel_array_layer| {
+                        let mut texture_base = base.clone();
+                        texture_base.array_layer += rel_array_layer;
+                        let region = hal::BufferTextureCopy {
+                            buffer_layout: wgt::ImageDataLayout {
+                                offset: stage_offset
+                                    + rel_array_layer as u64 * bytes_per_stage_layer,
+                                bytes_per_row: NonZeroU32::new(staging.stage_bytes_per_row),
+                                rows_per_image: NonZeroU32::new(staging.block_rows_per_image),
+                            },
+                            texture_base,
+                            size: copy_size,
+                        };
+                        split_texture_copy(region, &format_desc, &max_copy_extent)
+                    });
+                    let marker = copy_debug_marker(device.copy_debug_markers, || {
+                        format!(
+                            "write_batch -> {:?}, {}x{}x{}",
+                            destination.texture, copy_size.width, copy_size.height, copy_size.depth
+                        )
+                    });
+                    let encoder = device.pending_writes.activate();
+                    unsafe {
+                        if let Some(ref marker) = marker {
+                            encoder.begin_debug_marker(marker);
+                        }
+                        encoder
+                            .transition_textures(transition.map(|pending| pending.into_hal(dst)));
+                        encoder.copy_buffer_to_texture(&stage.buffer, dst_raw, regions);
+                        if marker.is_some() {
+                            encoder.end_debug_marker();
+                        }
+                    }
+                    device
+                        .pending_writes
+                        .dst_textures
+                        .insert(destination.texture);
+                }
+                _ => {}
+            }
+        }
+        drop(trackers);
+        device.pending_writes.consume(stage);
+
+        // Ensure the overwritten bytes are marked as initialized so they don't need to be nulled prior to mapping or binding.
+        for op in ops.iter() {
+            if let WriteOp::Buffer {
+                buffer_id,
+                offset,
+                data,
+            } = *op
+            {
+                let dst = buffer_guard.get_mut(buffer_id).unwrap();
+                dst.initialization_status
+                    .clear(offset..(offset + data.len() as wgt::BufferAddress));
+            }
+        }
+
         Ok(())
     }
 
@@ -590,22 +2937,264 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
         queue_id: id::QueueId,
         command_buffer_ids: &[id::CommandBufferId],
     ) -> Result<(), QueueSubmitError> {
+        self.queue_submit_impl::<A>(queue_id, Some(command_buffer_ids), None, CleanupMode::Full)
+            .map(|_| ())
+    }
+
+    /// Block until the GPU is done with all the writes made on the queue so far.
+    ///
+    /// Pending writes are flushed in a submission of their own first. Unlike
+    /// waiting for the device to be idle, this doesn't wait for command buffers
+    /// submitted after the last writes.
+    ///
+    /// If the queue runs out of memory for that submission, `OutOfMemory` is
+    /// returned, and the writes are retried with the next submission.
+    pub fn queue_wait_for_writes<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+    ) -> Result<(), WaitIdleError> {
+        // Checking for writes and flushing them is done under the same lock.
+        let submit_index =
+            match self.queue_submit_impl::<A>(queue_id, None, None, CleanupMode::Full) {
+                Ok((submit_index, _)) => submit_index,
+                Err(QueueSubmitError::Queue(err)) => return Err(err.into()),
+                // The writes are kept for a retry, but they aren't done.
+                Err(QueueSubmitError::OutOfMemory(_)) => {
+                    return Err(DeviceError::OutOfMemory.into())
+                }
+                Err(QueueSubmitError::StuckGpu) => return Err(WaitIdleError::StuckGpu),
+                // These are only about the submitted command buffers, and there are none.
+                Err(QueueSubmitError::DestroyedBuffer(_))
+                | Err(QueueSubmitError::DestroyedTexture(_))
+                | Err(QueueSubmitError::Unmap(_))
+                | Err(QueueSubmitError::SwapChainOutputDropped(_))
+                | Err(QueueSubmitError::SwapChainOutputPresented(_))
+                | Err(QueueSubmitError::NothingToRetry) => {
+                    unreachable!("flushing pending writes failed without command buffers")
+                }
+            };
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, mut token) = hub.devices.read(&mut token);
+        let device = device_guard
+            .get(queue_id)
+            .map_err(|_| DeviceError::Invalid)?;
+        device.wait_for_submit(submit_index, &mut token)
+    }
+
+    /// Submit command buffers and writes, executing on the GPU in the order of `steps`.
+    ///
+    /// Writes are always recorded at the time they are made, and run before
+    /// the next submitted command buffers. To run writes after some command
+    /// buffers, each group of command buffers is submitted on its own, with
+    /// the writes of the steps before it, and any writes of the final steps
+    /// are flushed in a submission of their own.
+    ///
+    /// Writes made before this call run before all the steps. If a step fails,
+    /// the steps before it are already submitted.
+    pub fn queue_submit_ordered<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        steps: &[SubmitStep],
+    ) -> Result<(), QueueSubmitOrderedError> {
+        let mut writes_pending = false;
+        for step in steps {
+            match *step {
+                SubmitStep::Writes(ops) => {
+                    self.queue_write_batch::<A>(queue_id, ops)?;
+                    writes_pending |= !ops.is_empty();
+                }
+                SubmitStep::CommandBuffers(command_buffer_ids) => {
+                    self.queue_submit::<A>(queue_id, command_buffer_ids)?;
+                    writes_pending = false;
+                }
+            }
+        }
+        if writes_pending {
+            self.queue_submit::<A>(queue_id, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Submit the command buffers, and return a future resolving when the GPU
+    /// is done with them.
+    ///
+    /// The future doesn't drive the device by itself: it only makes progress
+    /// when the submission is found to be done by `device_poll`, or by the
+    /// maintenance done in later submissions.
+    pub fn queue_submit_async<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        command_buffer_ids: &[id::CommandBufferId],
+    ) -> QueueSubmitFuture {
+        let submit_index = match self.queue_submit_impl::<A>(
+            queue_id,
+            Some(command_buffer_ids),
+            None,
+            CleanupMode::Full,
+        ) {
+            Ok((submit_index, _)) => submit_index,
+            Err(error) => {
+                return QueueSubmitFuture {
+                    state: SubmitFutureState::Failed(Some(error)),
+                }
+            }
+        };
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, mut token) = hub.devices.read(&mut token);
+        let device = match device_guard.get(queue_id) {
+            Ok(device) => device,
+            Err(_) => {
+                return QueueSubmitFuture {
+                    state: SubmitFutureState::Failed(Some(DeviceError::Invalid.into())),
+                }
+            }
+        };
+        let signal = Arc::new(WorkDoneSignal::default());
+        // If the submission is already done and retired, the signal completes right away.
+        device
+            .lock_life(&mut token)
+            .add_work_done_signal(submit_index, Arc::clone(&signal));
+        QueueSubmitFuture {
+            state: SubmitFutureState::Submitted(signal),
+        }
+    }
+
+    /// Submit the command buffers under a `label`, identifying the submission
+    /// in GPU profilers and API traces.
+    ///
+    /// The label is attached to the command buffer stitching the barriers in
+    /// front of each submitted one, and inserted there as a debug marker.
+    pub fn queue_submit_labeled<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        command_buffer_ids: &[id::CommandBufferId],
+        label: Option<&str>,
+    ) -> Result<(), QueueSubmitError> {
+        self.queue_submit_impl::<A>(queue_id, Some(command_buffer_ids), label, CleanupMode::Full)
+            .map(|_| ())
+    }
+
+    /// Submit the command buffers, spending at most about `cleanup_timeout_ms`
+    /// on the cleanup after the work the GPU is done with.
+    ///
+    /// Like with `queue_submit`, nothing is waited for. Returns
+    /// `SubmitCleanup::Partial` if the cleanup ran out of time, in which case
+    /// the remaining resources are destroyed by a later submission or
+    /// `device_poll`. The submission itself is done either way.
+    pub fn queue_submit_with_timeout<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        command_buffer_ids: &[id::CommandBufferId],
+        cleanup_timeout_ms: u32,
+    ) -> Result<SubmitCleanup, QueueSubmitError> {
+        self.queue_submit_impl::<A>(
+            queue_id,
+            Some(command_buffer_ids),
+            None,
+            CleanupMode::Bounded(cleanup_timeout_ms),
+        )
+        .map(|(_, cleanup)| cleanup)
+    }
+
+    /// Submit the command buffers for one-shot work.
+    ///
+    /// Every resource used by the command buffers is checked for destruction
+    /// along with the submission, so the ones the user has already dropped are
+    /// tied to it, and released as soon as it is found to be done by the next
+    /// `device_poll` or submission. The ones dropped later are released the
+    /// same way. Like `queue_submit`, this never waits for the GPU.
+    pub fn queue_submit_oneshot<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        command_buffer_ids: &[id::CommandBufferId],
+    ) -> Result<(), QueueSubmitError> {
+        self.queue_submit_impl::<A>(
+            queue_id,
+            Some(command_buffer_ids),
+            None,
+            CleanupMode::OneShot,
+        )
+        .map(|_| ())
+    }
+
+    /// Submit the command buffers along with the pending writes, and return
+    /// the index of the submission.
+    ///
+    /// With `None` for the command buffers, only the pending writes are
+    /// flushed, and nothing is submitted if there are none. The returned index
+    /// is then the one of the last submission with writes.
+    fn queue_submit_impl<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        command_buffer_ids: Option<&[id::CommandBufferId]>,
+        label: Option<&str>,
+        cleanup_mode: CleanupMode,
+    ) -> Result<(SubmissionIndex, SubmitCleanup), QueueSubmitError> {
         profiling::scope!("submit", "Queue");
 
         let hub = A::hub(self);
         let mut token = Token::root();
 
-        let callbacks = {
+        let (submit_index, callbacks, cleanup) = {
             let (mut device_guard, mut token) = hub.devices.write(&mut token);
             let device = device_guard
                 .get_mut(queue_id)
                 .map_err(|_| DeviceError::Invalid)?;
-            device.temp_suspected.clear();
-            device.active_submission_index += 1;
-            let submit_index = device.active_submission_index;
-            let mut active_executions = Vec::new();
+            let command_buffer_ids = match command_buffer_ids {
+                Some(command_buffer_ids) => command_buffer_ids,
+                None if !device.pending_writes.is_active && device.failed_submission.is_none() => {
+                    return Ok((
+                        device.pending_writes.last_submission,
+                        SubmitCleanup::Complete,
+                    ));
+                }
+                None => &[],
+            };
+            let transit_label = label.map(|label| format!("_Transit ({})", label));
 
+            // Start encoding the transitions first, so that running out of memory
+            // for them leaves all the command buffers registered, to be submitted again.
             {
+                let (mut command_buffer_guard, _) = hub.command_buffers.write(&mut token);
+                for &cmb_id in command_buffer_ids {
+                    if let Ok(cmdbuf) = command_buffer_guard.get_mut(cmb_id) {
+                        if cmdbuf.is_finished() {
+                            cmdbuf
+                                .begin_transit(transit_label.as_deref().unwrap_or("_Transit"))
+                                .map_err(|error| match DeviceError::from(error) {
+                                    DeviceError::OutOfMemory => {
+                                        QueueSubmitError::TransitOutOfMemory
+                                    }
+                                    other => other.into(),
+                                })?;
+                        }
+                    }
+                }
+            }
+
+            let submit_index = match device.failed_submission {
+                // The work gets appended to the failed submission, and retries it.
+                Some(ref failed) => failed.index,
+                None => {
+                    device.temp_suspected.clear();
+                    device.active_submission_index += 1;
+                    device.active_submission_index
+                }
+            };
+            let mut active_executions = Vec::new();
+
+            #[cfg(feature = "trace")]
+            if let (Some(ref trace), Some(label)) = (&device.trace, label) {
+                trace
+                    .lock()
+                    .add(Action::LabelSubmission(submit_index, label.to_string()));
+            }
+
+            let submission = {
                 let mut signal_swapchain_semaphores = SmallVec::<[_; 1]>::new();
                 let (mut swap_chain_guard, mut token) = hub.swap_chains.write(&mut token);
                 let (mut command_buffer_guard, mut token) = hub.command_buffers.write(&mut token);
@@ -658,10 +3247,16 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                         // optimize the tracked states
                         cmdbuf.trackers.optimize();
 
-                        for sc_id in cmdbuf.used_swap_chains.drain(..) {
+                        for (sc_id, frame) in cmdbuf.used_swap_chains.drain(..) {
                             let sc = &mut swap_chain_guard[sc_id.value];
-                            if sc.acquired_texture.is_none() {
-                                return Err(QueueSubmitError::SwapChainOutputDropped);
+                            if sc.acquired_texture.is_none() || frame != sc.acquired_frames {
+                                // A frame past the presented ones was never presented,
+                                // it belongs to a swap chain that got replaced since.
+                                return Err(if frame <= sc.presented_frames {
+                                    QueueSubmitError::SwapChainOutputPresented(sc_id.value.0)
+                                } else {
+                                    QueueSubmitError::SwapChainOutputDropped(sc_id.value.0)
+                                });
                             }
                             if sc.active_submission_index != submit_index {
                                 sc.active_submission_index = submit_index;
@@ -755,15 +3350,16 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                             }
                         }
 
+                        if let CleanupMode::OneShot = cleanup_mode {
+                            device.temp_suspected.add_trackers(&cmdbuf.trackers);
+                        }
+
                         let mut baked = cmdbuf.into_baked();
 
-                        // execute resource transitions
-                        unsafe {
-                            baked
-                                .encoder
-                                .begin_encoding(Some("_Transit"))
-                                .map_err(DeviceError::from)?
-                        };
+                        // execute resource transitions, in the encoding started above
+                        if let Some(label) = label {
+                            unsafe { baked.encoder.insert_debug_marker(label) };
+                        }
                         log::trace!("Stitching command buffer {:?} before submission", cmb_id);
                         //Note: stateless trackers are not merged:
                         // device already knows these resources exist.
@@ -795,61 +3391,348 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                     ref mut pending_writes,
                     ref mut queue,
                     ref mut fence,
+                    ref mut failed_submission,
                     ..
                 } = *device;
-                let refs = pending_writes
-                    .pre_submit()
-                    .into_iter()
-                    .chain(
-                        active_executions
-                            .iter()
-                            .flat_map(|pool_execution| pool_execution.cmd_buffers.iter()),
-                    )
-                    .collect::<Vec<_>>();
-                unsafe {
-                    queue
-                        .submit(&refs, Some((fence, submit_index)))
-                        .map_err(DeviceError::from)?;
-                }
-            }
+                let has_pending_writes = pending_writes.pre_submit(submit_index).is_some();
+                let mut submission = failed_submission
+                    .take()
+                    .unwrap_or_else(|| FailedSubmission::new(submit_index));
+                submission.append(
+                    has_pending_writes,
+                    active_executions,
+                    mem::take(&mut pending_writes.temp_resources),
+                );
+                // The command buffers are already unregistered, so they are kept
+                // around for a retry if this fails, instead of being destroyed.
+                submission.submit(
+                    failed_submission,
+                    &pending_writes.executing_command_buffers,
+                    |refs, index| unsafe { queue.submit(refs, Some((fence, index))) },
+                )?
+            };
+
+            let (callbacks, cleanup) = device.finish_submission(
+                &hub,
+                submit_index,
+                submission.executions,
+                submission.temp_resources,
+                cleanup_mode,
+                &mut token,
+            )?;
+            (submit_index, callbacks, cleanup)
+        };
+
+        // the map callbacks should execute with nothing locked!
+        drop(token);
+        super::fire_map_callbacks(callbacks);
+
+        Ok((submit_index, cleanup))
+    }
+
+    /// Retry the submission that last failed with `QueueSubmitError::OutOfMemory`.
+    ///
+    /// The command buffers of that submission are kept baked, so the caller
+    /// can free some memory and call this to execute them, in the same order
+    /// as they would have been. Any later submission retries it as well, with
+    /// its own work appended, so this is only needed when there is nothing
+    /// else to submit.
+    pub fn queue_retry_submission<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+    ) -> Result<(), QueueSubmitError> {
+        profiling::scope!("retry_submission", "Queue");
+
+        let hub = A::hub(self);
+        let mut token = Token::root();
+
+        let callbacks = {
+            let (mut device_guard, mut token) = hub.devices.write(&mut token);
+            let device = device_guard
+                .get_mut(queue_id)
+                .map_err(|_| DeviceError::Invalid)?;
+            let failed = device
+                .failed_submission
+                .take()
+                .ok_or(QueueSubmitError::NothingToRetry)?;
+
+            let super::Device {
+                ref pending_writes,
+                ref mut queue,
+                ref mut fence,
+                ref mut failed_submission,
+                ..
+            } = *device;
+            let failed = failed.submit(
+                failed_submission,
+                &pending_writes.executing_command_buffers,
+                |refs, index| unsafe { queue.submit(refs, Some((fence, index))) },
+            )?;
+
+            let (callbacks, _) = device.finish_submission(
+                &hub,
+                failed.index,
+                failed.executions,
+                failed.temp_resources,
+                CleanupMode::Full,
+                &mut token,
+            )?;
+            callbacks
+        };
+
+        // the map callbacks should execute with nothing locked!
+        drop(token);
+        super::fire_map_callbacks(callbacks);
+
+        Ok(())
+    }
+
+    /// Returns the alignment of the bytes per row that `queue_write_texture`
+    /// uses when staging data of the given format.
+    ///
+    /// Source data laid out with this row pitch can be staged without repacking.
+    pub fn texture_bytes_per_row_alignment<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        format: wgt::TextureFormat,
+    ) -> Result<u32, InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(device_id).map_err(|_| InvalidDevice)?;
+
+        Ok(device.bytes_per_row_alignment(format))
+    }
+
+    /// Choose how `queue_write_texture` repacks source rows on this device.
+    pub fn device_set_write_texture_repack<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        repack: WriteTextureRepack,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.write_texture_repack = repack;
+        Ok(())
+    }
+
+    /// Choose what happens to unsubmitted writes when this device is dropped.
+    pub fn device_set_unsubmitted_writes<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        policy: UnsubmittedWrites,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.unsubmitted_writes = policy;
+        Ok(())
+    }
+
+    /// Choose what `queue_write_buffer` does when the buffer may still be
+    /// used by earlier work on the GPU.
+    pub fn device_set_write_after_read<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        policy: WriteAfterRead,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.write_after_read = policy;
+        Ok(())
+    }
+
+    /// Record the copies in debug groups labeled with their source, destination
+    /// and size, so that they can be told apart in GPU captures.
+    ///
+    /// This applies to the queue writes, and to the copies of the command
+    /// encoders created afterwards. Backends without debug markers ignore them.
+    pub fn device_set_copy_debug_markers<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        enabled: bool,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.copy_debug_markers = enabled;
+        Ok(())
+    }
+
+    /// Let `queue_write_buffer` fill the buffer with zeros instead of staging
+    /// the data, when all of it is zero and it's at least `threshold` bytes.
+    ///
+    /// This saves the bandwidth of the staging copy, at the cost of scanning
+    /// the data of the writes that are large enough. `None` disables it,
+    /// which is the default.
+    pub fn device_set_zero_write_fill_threshold<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        threshold: Option<wgt::BufferAddress>,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        #[cfg(feature = "trace")]
+        if let Some(ref trace) = device.trace {
+            trace
+                .lock()
+                .add(Action::SetZeroWriteFillThreshold(threshold));
+        }
+        device.zero_write_fill_threshold = threshold;
+        Ok(())
+    }
+
+    /// Returns the largest copies that the queue writes of this device perform at once.
+    pub fn device_max_copy_size<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+    ) -> Result<CopyLimits, InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(device_id).map_err(|_| InvalidDevice)?;
+        Ok(device.copy_limits)
+    }
 
-            profiling::scope!("cleanup");
-            if let Some(pending_execution) = device.pending_writes.post_submit(
-                &device.command_allocator,
-                &device.raw,
-                &device.queue,
-            ) {
-                active_executions.push(pending_execution);
-            }
+    /// Lower the largest copies that the queue writes of this device perform
+    /// at once, for drivers that fail on large copies.
+    pub fn device_set_max_copy_size<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        limits: CopyLimits,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.copy_limits = limits;
+        Ok(())
+    }
 
-            // this will register the new submission to the life time tracker
-            let mut pending_write_resources = mem::take(&mut device.pending_writes.temp_resources);
-            device.lock_life(&mut token).track_submission(
-                submit_index,
-                pending_write_resources.drain(..),
-                active_executions,
-            );
+    /// Override how long the device waits for the GPU before considering it stuck.
+    ///
+    /// Waits that exceed the timeout, like in `device_poll` with `force_wait`,
+    /// fail with `WaitIdleError::StuckGpu`. `None` waits for as long as it
+    /// takes instead, which is useful when stepping through shaders in a GPU
+    /// debugger. `Some(timeout_ms)` restores a finite timeout.
+    pub fn device_set_stuck_gpu_timeout<A: HalApi>(
+        &self,
+        device_id: id::DeviceId,
+        timeout_ms: Option<u32>,
+    ) -> Result<(), InvalidDevice> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (mut device_guard, _) = hub.devices.write(&mut token);
+        let device = device_guard.get_mut(device_id).map_err(|_| InvalidDevice)?;
+        device.stuck_gpu_timeout_ms = timeout_ms;
+        Ok(())
+    }
 
-            // This will schedule destruction of all resources that are no longer needed
-            // by the user but used in the command stream, among other things.
-            let callbacks = match device.maintain(&hub, false, &mut token) {
-                Ok(callbacks) => callbacks,
-                Err(WaitIdleError::Device(err)) => return Err(QueueSubmitError::Queue(err)),
-                Err(WaitIdleError::StuckGpu) => return Err(QueueSubmitError::StuckGpu),
-            };
+    /// Returns the time spent mapping staging buffers for the uploads made on
+    /// this queue so far.
+    ///
+    /// A large share of map time hints at driver stalls, which keeping the
+    /// staging memory persistently mapped would avoid.
+    #[cfg(feature = "staging-stats")]
+    pub fn queue_staging_map_stats<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+    ) -> Result<StagingMapStats, InvalidQueue> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(queue_id).map_err(|_| InvalidQueue)?;
+        Ok(device.staging_map_stats)
+    }
 
-            device.pending_writes.temp_resources = pending_write_resources;
-            device.temp_suspected.clear();
-            device.lock_life(&mut token).post_submit();
+    /// Returns the staging buffer used by the last upload on this queue, or
+    /// `None` if nothing was staged yet.
+    ///
+    /// This is meant for tests checking which path an upload took.
+    #[cfg(feature = "staging-stats")]
+    pub fn queue_last_staging_info<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+    ) -> Result<Option<StagingInfo>, InvalidQueue> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(queue_id).map_err(|_| InvalidQueue)?;
+        Ok(device.last_staging)
+    }
 
-            callbacks
-        };
+    /// Writes `data` at the start of `buffer_id`, submits the write, waits
+    /// for the device to finish it, and returns the bytes read back by mapping
+    /// the buffer.
+    ///
+    /// The buffer needs the `COPY_DST` and `MAP_READ` usages, and the length of
+    /// `data` has to be a multiple of `COPY_BUFFER_ALIGNMENT`. This is meant for
+    /// tests checking that data survives the round-trip, so any error on the way
+    /// panics instead of being returned.
+    #[cfg(feature = "testing")]
+    pub fn queue_upload_and_readback<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+        buffer_id: id::BufferId,
+        data: &[u8],
+    ) -> Vec<u8> {
+        unsafe extern "C" fn map_callback(
+            status: crate::resource::BufferMapAsyncStatus,
+            user_data: *mut u8,
+        ) {
+            *(user_data as *mut bool) =
+                matches!(status, crate::resource::BufferMapAsyncStatus::Success);
+        }
 
-        // the map callbacks should execute with nothing locked!
-        drop(token);
-        super::fire_map_callbacks(callbacks);
+        let size = data.len() as wgt::BufferAddress;
+        self.queue_write_buffer::<A>(queue_id, buffer_id, 0, data)
+            .expect("failed to write the buffer");
+        self.queue_submit::<A>(queue_id, &[])
+            .expect("failed to submit the write");
 
-        Ok(())
+        let mut mapped = false;
+        self.buffer_map_async::<A>(
+            buffer_id,
+            0..size,
+            crate::resource::BufferMapOperation {
+                host: super::HostMap::Read,
+                callback: map_callback,
+                user_data: &mut mapped as *mut bool as *mut u8,
+            },
+        )
+        .expect("failed to map the buffer");
+        self.device_poll::<A>(queue_id, true)
+            .expect("failed to wait for the device");
+        assert!(mapped, "buffer {:?} failed to map", buffer_id);
+
+        let (ptr, len) = self
+            .buffer_get_mapped_range::<A>(buffer_id, 0, Some(size))
+            .expect("failed to get the mapped range");
+        let bytes = unsafe { slice::from_raw_parts(ptr, len as usize) }.to_vec();
+        self.buffer_unmap::<A>(buffer_id)
+            .expect("failed to unmap the buffer");
+        bytes
+    }
+
+    /// Returns the index of the last submission made on the queue,
+    /// or 0 if nothing was submitted yet.
+    pub fn queue_last_submission_index<A: HalApi>(
+        &self,
+        queue_id: id::QueueId,
+    ) -> Result<SubmissionIndex, InvalidQueue> {
+        let hub = A::hub(self);
+        let mut token = Token::root();
+        let (device_guard, _) = hub.devices.read(&mut token);
+        let device = device_guard.get(queue_id).map_err(|_| InvalidQueue)?;
+        Ok(device.active_submission_index)
     }
 
     pub fn queue_get_timestamp_period<A: HalApi>(
@@ -866,6 +3749,86 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
     }
 }
 
+/// Checks that a buffer can be left in `usage` on its own: either a set of
+/// read-only usages, or a single writable one, all within `allowed`.
+fn is_valid_buffer_state(usage: hal::BufferUses, allowed: hal::BufferUses) -> bool {
+    let compatible = hal::BufferUses::INCLUSIVE.contains(usage)
+        || (hal::BufferUses::EXCLUSIVE.contains(usage) && usage.bits().count_ones() == 1);
+    !usage.is_empty() && compatible && allowed.contains(usage)
+}
+
+/// Checks that a texture can be left in `usage` on its own: either a set of
+/// read-only usages, or a single writable one, all within `allowed`.
+fn is_valid_texture_state(usage: hal::TextureUses, allowed: hal::TextureUses) -> bool {
+    let compatible = hal::TextureUses::INCLUSIVE.contains(usage)
+        || (hal::TextureUses::EXCLUSIVE.contains(usage) && usage.bits().count_ones() == 1);
+    !usage.is_empty() && compatible && allowed.contains(usage)
+}
+
+/// Splits a copy into a texture into copies that are no larger than
+/// `max_extent`, sourcing their texels from the same buffer data.
+///
+/// The pieces are made of whole blocks. If `max_extent` is smaller than a
+/// block, each piece copies a single block along that dimension.
+fn split_texture_copy(
+    region: hal::BufferTextureCopy,
+    format_desc: &wgt::TextureFormatInfo,
+    max_extent: &wgt::Extent3d,
+) -> SmallVec<[hal::BufferTextureCopy; 1]> {
+    let size = region.size;
+    if size.width <= max_extent.width
+        && size.height <= max_extent.height
+        && size.depth <= max_extent.depth_or_array_layers
+    {
+        return iter::once(region).collect();
+    }
+
+    let (block_width, block_height) = format_desc.block_dimensions;
+    let (block_width, block_height) = (block_width as u32, block_height as u32);
+    let block_size = format_desc.block_size as wgt::BufferAddress;
+    let step_width = (max_extent.width / block_width).max(1) * block_width;
+    let step_height = (max_extent.height / block_height).max(1) * block_height;
+    let step_depth = max_extent.depth_or_array_layers.max(1);
+    // Every piece reads the rows of the whole copy, with the same layout.
+    let bytes_per_row = region
+        .buffer_layout
+        .bytes_per_row
+        .map_or(0, |bpr| bpr.get() as wgt::BufferAddress);
+    let bytes_per_image = bytes_per_row
+        * region
+            .buffer_layout
+            .rows_per_image
+            .map_or(0, |rpi| rpi.get() as wgt::BufferAddress);
+
+    let mut pieces = SmallVec::new();
+    for z in (0..size.depth).step_by(step_depth as usize) {
+        for y in (0..size.height).step_by(step_height as usize) {
+            for x in (0..size.width).step_by(step_width as usize) {
+                let mut texture_base = region.texture_base.clone();
+                texture_base.origin.x += x;
+                texture_base.origin.y += y;
+                texture_base.origin.z += z;
+                pieces.push(hal::BufferTextureCopy {
+                    buffer_layout: wgt::ImageDataLayout {
+                        offset: region.buffer_layout.offset
+                            + z as wgt::BufferAddress * bytes_per_image
+                            + (y / block_height) as wgt::BufferAddress * bytes_per_row
+                            + (x / block_width) as wgt::BufferAddress * block_size,
+                        ..region.buffer_layout
+                    },
+                    texture_base,
+                    size: hal::CopyExtent {
+                        width: step_width.min(size.width - x),
+                        height: step_height.min(size.height - y),
+                        depth: step_depth.min(size.depth - z),
+                    },
+                });
+            }
+        }
+    }
+    pieces
+}
+
 fn get_lowest_common_denom(a: u32, b: u32) -> u32 {
     let gcd = if a >= b {
         get_greatest_common_divisor(a, b)
@@ -895,6 +3858,368 @@ fn align_to(value: u32, alignment: u32) -> u32 {
     }
 }
 
+fn align_to_address(value: wgt::BufferAddress, alignment: u32) -> wgt::BufferAddress {
+    match value % alignment as wgt::BufferAddress {
+        0 => value,
+        other => value - other + alignment as wgt::BufferAddress,
+    }
+}
+
+/// Expand an uninitialized range of a buffer to the 4 byte alignment that
+/// `fill_buffer` requires.
+///
+/// Only the end of a buffer can be unaligned, since all writes are aligned.
+/// The expanded range never goes past the buffer's allocation, which is
+/// rounded up to `COPY_BUFFER_ALIGNMENT` when the buffer is created.
+fn align_init_range(
+    range: Range<wgt::BufferAddress>,
+    buffer_size: wgt::BufferAddress,
+) -> Range<wgt::BufferAddress> {
+    let alignment = wgt::COPY_BUFFER_ALIGNMENT;
+    let start = range.start - range.start % alignment;
+    let end = align_to_address(range.end, alignment as u32)
+        .min(align_to_address(buffer_size, alignment as u32));
+    start..end
+}
+
+/// Source data split across several slices, read as if they were concatenated.
+#[derive(Debug)]
+struct ChunkedSource<'a> {
+    chunks: &'a [&'a [u8]],
+    /// Number of bytes at the start of the data to ignore.
+    base: usize,
+    /// Index of the chunk that the last read ended in.
+    index: usize,
+    /// Offset of that chunk in the concatenated data.
+    chunk_start: usize,
+}
+
+impl<'a> ChunkedSource<'a> {
+    fn new(chunks: &'a [&'a [u8]]) -> Self {
+        Self {
+            chunks,
+            base: 0,
+            index: 0,
+            chunk_start: 0,
+        }
+    }
+
+    /// Make offsets relative to `bytes` further into the data.
+    fn skip(&mut self, bytes: usize) {
+        self.base += bytes;
+    }
+
+    /// Number of bytes after the skipped ones.
+    fn len(&self) -> usize {
+        let total: usize = self.chunks.iter().map(|chunk| chunk.len()).sum();
+        total.saturating_sub(self.base)
+    }
+
+    /// Copy the bytes at `offset` into `dst`.
+    ///
+    /// Reads are cheapest when done at increasing offsets.
+    fn read(&mut self, offset: usize, dst: &mut [u8]) {
+        let mut pos = self.base + offset;
+        if pos < self.chunk_start {
+            self.index = 0;
+            self.chunk_start = 0;
+        }
+        let mut written = 0;
+        while written < dst.len() {
+            let chunk = self.chunks[self.index];
+            if pos >= self.chunk_start + chunk.len() {
+                self.chunk_start += chunk.len();
+                self.index += 1;
+                continue;
+            }
+            let start = pos - self.chunk_start;
+            let count = (chunk.len() - start).min(dst.len() - written);
+            dst[written..written + count].copy_from_slice(&chunk[start..start + count]);
+            written += count;
+            pos += count;
+        }
+    }
+}
+
+/// Repacking of the rows of a linear texture upload into a different row pitch,
+/// keeping the same number of rows per image.
+///
+/// Only the `height_blocks` rows of each image are copied. If there are more
+/// rows per image, the padding rows are skipped in the source, and left
+/// untouched in the destination.
+#[derive(Clone, Copy, Debug)]
+struct RowRepack {
+    src_bytes_per_row: u32,
+    dst_bytes_per_row: u32,
+    bytes_in_row: u32,
+    height_blocks: u32,
+    block_rows_per_image: u32,
+    layers: u32,
+}
+
+impl RowRepack {
+    fn copy(&self, src: &[u8], dst: &mut [u8]) {
+        self.copy_chunked(&mut ChunkedSource::new(&[src]), dst, !0, |_| {});
+    }
+
+    /// Copy the rows in chunks of at most `chunk_rows`, calling `on_chunk`
+    /// with the range of `dst` written by each chunk once it's done.
+    fn copy_chunked(
+        &self,
+        src: &mut ChunkedSource,
+        dst: &mut [u8],
+        chunk_rows: u32,
+        mut on_chunk: impl FnMut(Range<usize>),
+    ) {
+        let bytes_in_row = self.bytes_in_row as usize;
+        let mut chunk: Option<Range<usize>> = None;
+        let mut rows_in_chunk = 0;
+        for layer in 0..self.layers {
+            let rows_offset = layer * self.block_rows_per_image;
+            for row in rows_offset..rows_offset + self.height_blocks {
+                let src_start = row as usize * self.src_bytes_per_row as usize;
+                let dst_start = row as usize * self.dst_bytes_per_row as usize;
+                src.read(src_start, &mut dst[dst_start..dst_start + bytes_in_row]);
+
+                let start = chunk.as_ref().map_or(dst_start, |chunk| chunk.start);
+                chunk = Some(start..dst_start + bytes_in_row);
+                rows_in_chunk += 1;
+                if rows_in_chunk == chunk_rows {
+                    on_chunk(chunk.take().unwrap());
+                    rows_in_chunk = 0;
+                }
+            }
+        }
+        if let Some(chunk) = chunk {
+            on_chunk(chunk);
+        }
+    }
+}
+
+#[test]
+fn test_valid_resource_states() {
+    let allowed =
+        hal::BufferUses::COPY_SRC | hal::BufferUses::VERTEX | hal::BufferUses::STORAGE_WRITE;
+    assert!(is_valid_buffer_state(
+        hal::BufferUses::COPY_SRC | hal::BufferUses::VERTEX,
+        allowed
+    ));
+    assert!(is_valid_buffer_state(
+        hal::BufferUses::STORAGE_WRITE,
+        allowed
+    ));
+    assert!(!is_valid_buffer_state(hal::BufferUses::empty(), allowed));
+    assert!(!is_valid_buffer_state(hal::BufferUses::INDEX, allowed));
+    assert!(!is_valid_buffer_state(
+        hal::BufferUses::COPY_SRC | hal::BufferUses::STORAGE_WRITE,
+        allowed
+    ));
+
+    let allowed = hal::TextureUses::SAMPLED | hal::TextureUses::COPY_DST;
+    assert!(is_valid_texture_state(hal::TextureUses::SAMPLED, allowed));
+    assert!(is_valid_texture_state(hal::TextureUses::COPY_DST, allowed));
+    assert!(!is_valid_texture_state(
+        hal::TextureUses::SAMPLED | hal::TextureUses::COPY_DST,
+        allowed
+    ));
+    assert!(!is_valid_texture_state(
+        hal::TextureUses::COLOR_TARGET,
+        allowed
+    ));
+}
+
+#[test]
+fn test_staging_pool_waits_for_submission() {
+    let mut pool = StagingPool::new();
+    assert!(pool.release("a", 256).is_none());
+    // Not submitted yet, so still used by the pending writes.
+    assert_eq!(pool.acquire(256, 10), None);
+
+    pool.submit(3);
+    assert_eq!(pool.acquire(256, 2), None);
+    assert_eq!(pool.acquire(512, 3), None);
+    assert_eq!(pool.acquire(128, 3), Some(("a", 256)));
+    assert!(!pool.has_submitted());
+}
+
+#[test]
+fn test_staging_pool_rapid_recycling() {
+    let mut pool = StagingPool::new();
+    let mut in_flight = Vec::new();
+    let mut next_buffer = 0;
+    // Submit many uploads while the GPU lags a few submissions behind.
+    for submit_index in 1..100 {
+        let last_done = submit_index.saturating_sub(4);
+        let buffer = match pool.acquire(64, last_done) {
+            Some((buffer, _)) => buffer,
+            None => {
+                next_buffer += 1;
+                next_buffer
+            }
+        };
+        assert!(
+            !in_flight
+                .iter()
+                .any(|&(b, index)| b == buffer && index > last_done),
+            "buffer {} is still used by an in-flight submission",
+            buffer
+        );
+        in_flight.push((buffer, submit_index));
+        assert!(pool.release(buffer, 64).is_none());
+        pool.submit(submit_index);
+    }
+    // Buffers do get recycled, only as many as are in flight at once.
+    assert!(next_buffer <= 5);
+
+    let mut pool = StagingPool::new();
+    assert_eq!(
+        pool.release(1, STAGING_POOL_MAX_BUFFER_SIZE + 1),
+        Some(1),
+        "large buffers aren't pooled"
+    );
+    for buffer in 0..STAGING_POOL_SIZE {
+        assert!(pool.release(buffer, 64).is_none());
+    }
+    assert_eq!(pool.release(STAGING_POOL_SIZE, 64), Some(STAGING_POOL_SIZE));
+    assert_eq!(pool.drain().count(), STAGING_POOL_SIZE);
+}
+
+#[test]
+fn test_copy_limits() {
+    let limits = CopyLimits {
+        max_buffer_copy_bytes: 256,
+        max_texture_copy_extent: wgt::Extent3d {
+            width: 64,
+            height: 32,
+            depth_or_array_layers: 1,
+        },
+    };
+    assert!(limits.check_buffer_copy(256).is_ok());
+    assert!(matches!(
+        limits.check_buffer_copy(260),
+        Err(TransferError::BufferCopyTooLarge {
+            size: 260,
+            max: 256
+        })
+    ));
+
+    let defaults = CopyLimits::from_limits(&wgt::Limits::default());
+    assert_eq!(defaults.max_buffer_copy_bytes, wgt::BufferAddress::MAX);
+    assert!(
+        defaults.max_texture_copy_extent.width >= wgt::Limits::default().max_texture_dimension_2d
+    );
+}
+
+#[test]
+fn test_split_texture_copy() {
+    use std::collections::HashMap;
+
+    // Maps every texel block written by the copies to the buffer offset it's read from.
+    fn blocks(
+        regions: &[hal::BufferTextureCopy],
+        format_desc: &wgt::TextureFormatInfo,
+    ) -> HashMap<(u32, u32, u32), wgt::BufferAddress> {
+        let (block_width, block_height) = format_desc.block_dimensions;
+        let (block_width, block_height) = (block_width as u32, block_height as u32);
+        let mut blocks = HashMap::new();
+        for region in regions {
+            let layout = &region.buffer_layout;
+            let bytes_per_row = layout.bytes_per_row.unwrap().get() as wgt::BufferAddress;
+            let rows_per_image = layout.rows_per_image.unwrap().get() as wgt::BufferAddress;
+            let origin = region.texture_base.origin;
+            for z in 0..region.size.depth {
+                for y in (0..region.size.height).step_by(block_height as usize) {
+                    for x in (0..region.size.width).step_by(block_width as usize) {
+                        let offset = layout.offset
+                            + z as wgt::BufferAddress * rows_per_image * bytes_per_row
+                            + (y / block_height) as wgt::BufferAddress * bytes_per_row
+                            + (x / block_width) as wgt::BufferAddress
+                                * format_desc.block_size as wgt::BufferAddress;
+                        let texel = (origin.x + x, origin.y + y, origin.z + z);
+                        assert!(
+                            blocks.insert(texel, offset).is_none(),
+                            "{:?} copied twice",
+                            texel
+                        );
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    let cases = [
+        // A 2D texture much wider and taller than the limit.
+        (
+            wgt::TextureFormat::Rgba8Unorm,
+            hal::CopyExtent {
+                width: 10,
+                height: 7,
+                depth: 1,
+            },
+            wgt::Extent3d {
+                width: 4,
+                height: 3,
+                depth_or_array_layers: 1,
+            },
+        ),
+        // Compressed blocks, with a partial block at the edge of the mip.
+        (
+            wgt::TextureFormat::Bc1RgbaUnorm,
+            hal::CopyExtent {
+                width: 13,
+                height: 9,
+                depth: 1,
+            },
+            wgt::Extent3d {
+                width: 6,
+                height: 8,
+                depth_or_array_layers: 1,
+            },
+        ),
+        // Slices of a 3D texture.
+        (
+            wgt::TextureFormat::R32Float,
+            hal::CopyExtent {
+                width: 4,
+                height: 4,
+                depth: 5,
+            },
+            wgt::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 2,
+            },
+        ),
+    ];
+    for &(format, size, ref max_extent) in cases.iter() {
+        let format_desc = format.describe();
+        let region = hal::BufferTextureCopy {
+            buffer_layout: wgt::ImageDataLayout {
+                offset: 512,
+                bytes_per_row: NonZeroU32::new(256),
+                rows_per_image: NonZeroU32::new(8),
+            },
+            texture_base: hal::TextureCopyBase {
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgt::Origin3d { x: 4, y: 4, z: 0 },
+                aspect: hal::FormatAspects::COLOR,
+            },
+            size,
+        };
+        let whole = blocks(&[region.clone()], &format_desc);
+        let pieces = split_texture_copy(region, &format_desc, max_extent);
+        assert!(pieces.len() > 1);
+        for piece in pieces.iter() {
+            assert!(piece.size.width <= max_extent.width);
+            assert!(piece.size.height <= max_extent.height);
+            assert!(piece.size.depth <= max_extent.depth_or_array_layers);
+        }
+        assert_eq!(blocks(&pieces, &format_desc), whole);
+    }
+}
+
 #[test]
 fn test_lcd() {
     assert_eq!(get_lowest_common_denom(2, 2), 2);
@@ -909,3 +4234,506 @@ fn test_gcd() {
     assert_eq!(get_greatest_common_divisor(6, 4), 2);
     assert_eq!(get_greatest_common_divisor(7, 7), 7);
 }
+
+#[test]
+fn test_align_init_range() {
+    // Aligned ranges are left as they are.
+    assert_eq!(align_init_range(4..12, 16), 4..12);
+    // A 2 byte buffer is initialized as a whole word.
+    assert_eq!(align_init_range(0..2, 2), 0..4);
+    // The unaligned tail of a buffer.
+    assert_eq!(align_init_range(8..10, 10), 8..12);
+    assert_eq!(align_init_range(5..10, 10), 4..12);
+}
+
+#[test]
+fn test_repack_padded_rows_per_image() {
+    // Two 3x2 images of 4-byte texels, with 4 rows per image and a 16 byte
+    // row pitch in the source, repacked into a 256 byte row pitch.
+    let (bytes_in_row, height_blocks, block_rows_per_image, layers) = (12, 2, 4, 2);
+    let src_bytes_per_row = 16;
+    let dst_bytes_per_row = 256;
+    // The source ends right after the last real row.
+    let src_len = (block_rows_per_image * (layers - 1) + height_blocks - 1) * src_bytes_per_row
+        + bytes_in_row;
+    let src = (0..src_len).map(|i| i as u8).collect::<Vec<_>>();
+    let mut dst =
+        vec![0xFFu8; (dst_bytes_per_row * (block_rows_per_image + height_blocks)) as usize];
+    let repack = RowRepack {
+        src_bytes_per_row,
+        dst_bytes_per_row,
+        bytes_in_row,
+        height_blocks,
+        block_rows_per_image,
+        layers,
+    };
+    repack.copy(&src, &mut dst);
+
+    for row in 0..block_rows_per_image + height_blocks {
+        let dst_row = &dst[(row * dst_bytes_per_row) as usize..][..dst_bytes_per_row as usize];
+        if row % block_rows_per_image < height_blocks {
+            let src_start = (row * src_bytes_per_row) as usize;
+            assert_eq!(
+                &dst_row[..bytes_in_row as usize],
+                &src[src_start..src_start + bytes_in_row as usize]
+            );
+            assert!(dst_row[bytes_in_row as usize..].iter().all(|&b| b == 0xFF));
+        } else {
+            // Padding rows are not copied.
+            assert!(dst_row.iter().all(|&b| b == 0xFF));
+        }
+    }
+}
+
+#[test]
+fn test_repack_chunked_matches_single_pass() {
+    let repack = RowRepack {
+        src_bytes_per_row: 20,
+        dst_bytes_per_row: 256,
+        bytes_in_row: 16,
+        height_blocks: 5,
+        block_rows_per_image: 7,
+        layers: 3,
+    };
+    let src_len = ((repack.layers - 1) * repack.block_rows_per_image + repack.height_blocks)
+        * repack.src_bytes_per_row;
+    let src = (0..src_len).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+    let dst_len = (((repack.layers - 1) * repack.block_rows_per_image + repack.height_blocks)
+        * repack.dst_bytes_per_row) as usize;
+
+    let mut single_pass = vec![0u8; dst_len];
+    repack.copy(&src, &mut single_pass);
+
+    for &chunk_rows in [1, 2, 4, 5, 6, 100].iter() {
+        let mut chunked = vec![0u8; dst_len];
+        let mut chunks = Vec::new();
+        repack.copy_chunked(
+            &mut ChunkedSource::new(&[&src]),
+            &mut chunked,
+            chunk_rows,
+            |range| chunks.push(range),
+        );
+        assert_eq!(chunked, single_pass);
+
+        // Chunks are in order, don't overlap, and cover every copied row.
+        let total_rows = repack.layers * repack.height_blocks;
+        assert_eq!(
+            chunks.len() as u32,
+            (total_rows + chunk_rows - 1) / chunk_rows
+        );
+        for pair in chunks.windows(2) {
+            assert!(pair[0].end <= pair[1].start);
+        }
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, dst_len - 256 + 16);
+    }
+}
+
+#[test]
+fn test_repack_from_chunks() {
+    let repack = RowRepack {
+        src_bytes_per_row: 20,
+        dst_bytes_per_row: 256,
+        bytes_in_row: 16,
+        height_blocks: 4,
+        block_rows_per_image: 4,
+        layers: 2,
+    };
+    let src = (0..160).map(|i| i as u8).collect::<Vec<_>>();
+    let mut expected = vec![0u8; 256 * 8];
+    repack.copy(&src, &mut expected);
+
+    // Chunks splitting rows, whole rows, and empty ones.
+    let chunks = [&src[..7], &src[7..7], &src[7..40], &src[40..41], &src[41..]];
+    let mut repacked = vec![0u8; 256 * 8];
+    repack.copy_chunked(&mut ChunkedSource::new(&chunks), &mut repacked, !0, |_| {});
+    assert_eq!(repacked, expected);
+
+    // Offsets are relative to the skipped bytes.
+    let mut source = ChunkedSource::new(&chunks);
+    source.skip(5);
+    assert_eq!(source.len(), 155);
+    let mut bytes = [0u8; 4];
+    source.read(36, &mut bytes);
+    assert_eq!(bytes, [41, 42, 43, 44]);
+    source.read(0, &mut bytes);
+    assert_eq!(bytes, [5, 6, 7, 8]);
+}
+
+#[test]
+fn test_texture_upload_stats() {
+    // 4 rows of 10 RGBA8 texels, staged with a 256 byte pitch.
+    let stats = TextureUploadStats::new(40 * 4, 256 * 4);
+    assert_eq!(stats.tight_bytes, 160);
+    assert_eq!(stats.staged_bytes, 1024);
+    assert!((stats.padding_ratio - 6.4).abs() < 1e-6);
+    assert_eq!(stats.estimated_ns, estimate_transfer_ns(1024));
+    let stats = TextureUploadStats::new(64, 64);
+    assert!((stats.padding_ratio - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_write_accounting() {
+    // A reused staging buffer of 2048 bytes, for rows padded to 1024 bytes.
+    let stats = TextureUploadStats {
+        staging_capacity: 2048,
+        ..TextureUploadStats::new(40 * 4, 256 * 4)
+    };
+    assert_eq!(
+        WriteAccounting::from(stats),
+        WriteAccounting {
+            staged_bytes: 2048,
+            copied_bytes: 160,
+        }
+    );
+    // Without anything else to account for, the staging memory is the padded size.
+    let stats = TextureUploadStats::new(40 * 4, 256 * 4);
+    assert_eq!(WriteAccounting::from(stats).staged_bytes, 1024);
+}
+
+#[test]
+fn test_skip_init_buffer_needs_no_fill() {
+    use crate::id::TypedId as _;
+    let buffer_id = id::BufferId::zip(0, 1, wgt::Backend::Empty);
+    let read = MemoryInitTrackerAction {
+        id: buffer_id,
+        range: 0..64,
+        kind: MemoryInitKind::NeedsInitializedMemory,
+    };
+
+    let mut inits = RequiredBufferInits::default();
+    let mut status = MemoryInitTracker::new(256);
+    inits.add_action(&read, &mut status, 256, BufferInitPolicy::Zero);
+    assert_eq!(inits.map[&buffer_id], vec![0..64]);
+
+    // Reading a buffer that skips initialization doesn't fill it, but still
+    // marks the range as initialized.
+    let mut inits = RequiredBufferInits::default();
+    let mut status = MemoryInitTracker::new(256);
+    inits.add_action(&read, &mut status, 256, BufferInitPolicy::Skip);
+    assert!(inits.map.is_empty());
+    assert_eq!(status.drain(0..256).collect::<Vec<_>>(), vec![64..256]);
+}
+
+#[test]
+fn test_full_overwrite_skips_init() {
+    use crate::id::TypedId as _;
+    let buffer_id = id::BufferId::zip(0, 1, wgt::Backend::Empty);
+    let mut status = MemoryInitTracker::new(256);
+    status.clear(16..32);
+    status.clear(128..192);
+
+    // A copy overwriting the whole buffer records a single action for all of it.
+    let range = status.check_write(0..256, 256).unwrap();
+    assert_eq!(range, 0..256);
+    let write = MemoryInitTrackerAction {
+        id: buffer_id,
+        range,
+        kind: MemoryInitKind::ImplicitlyInitialized,
+    };
+    let mut inits = RequiredBufferInits::default();
+    inits.add_action(&write, &mut status, 256, BufferInitPolicy::Zero);
+    assert!(inits.map.is_empty());
+
+    // Reading it afterwards doesn't need any of it zeroed.
+    let read = MemoryInitTrackerAction {
+        id: buffer_id,
+        range: 0..256,
+        kind: MemoryInitKind::NeedsInitializedMemory,
+    };
+    inits.add_action(&read, &mut status, 256, BufferInitPolicy::Zero);
+    assert!(inits.map.get(&buffer_id).map_or(true, Vec::is_empty));
+}
+
+#[test]
+fn test_buffer_write_barriers() {
+    use crate::id::TypedId as _;
+    let buffer_id = id::BufferId::zip(0, 1, wgt::Backend::Empty);
+
+    // Disjoint writes need no barrier between them, overlapping ones do.
+    let mut ranges = BufferWriteRanges::default();
+    assert!(!ranges.track_write(buffer_id, 0..16));
+    assert!(!ranges.track_write(buffer_id, 16..32));
+    assert!(ranges.track_write(buffer_id, 8..24));
+    // The barrier ordered all the writes before it.
+    assert!(!ranges.track_write(buffer_id, 0..8));
+
+    // A transition of the buffer in between orders the writes as well, so
+    // the overlapping write doesn't need a barrier of its own.
+    let mut ranges = BufferWriteRanges::default();
+    assert!(!ranges.track_write(buffer_id, 0..16));
+    ranges.track_barrier(buffer_id);
+    assert!(!ranges.track_write(buffer_id, 0..16));
+    assert!(ranges.track_write(buffer_id, 0..16));
+}
+
+#[test]
+fn test_failed_submission_retry() {
+    let mut slot = None;
+
+    // Running out of memory keeps the submission around for a retry.
+    let mut failed = FailedSubmission::<hal::api::Empty>::new(3);
+    failed.append(true, Vec::new(), Vec::new());
+    let error = failed.keep_if_recoverable(&mut slot, DeviceError::OutOfMemory);
+    assert!(matches!(error, QueueSubmitError::OutOfMemory(3)));
+
+    // The next submission is appended to it, and fails the same way, so
+    // everything is kept again under the same index.
+    let mut failed = slot.take().unwrap();
+    failed.append(true, Vec::new(), Vec::new());
+    assert_eq!(failed.pending_writes, [0, 0]);
+    let error = failed.keep_if_recoverable(&mut slot, DeviceError::OutOfMemory);
+    assert!(matches!(error, QueueSubmitError::OutOfMemory(3)));
+    assert_eq!(slot.as_ref().map(FailedSubmission::index), Some(3));
+
+    // A lost device can't be recovered from by retrying.
+    let failed = slot.take().unwrap();
+    let error = failed.keep_if_recoverable(&mut slot, DeviceError::Lost);
+    assert!(matches!(error, QueueSubmitError::Queue(DeviceError::Lost)));
+    assert!(slot.is_none());
+}
+
+#[test]
+fn test_failed_submission_submit() {
+    type Empty = hal::api::Empty;
+    let context = unsafe {
+        <<Empty as hal::Api>::Instance as hal::Instance<Empty>>::init(&hal::InstanceDescriptor {
+            name: "test",
+            flags: hal::InstanceFlags::empty(),
+        })
+    }
+    .unwrap();
+    let mut raw = unsafe {
+        context.create_command_encoder(&hal::CommandEncoderDescriptor {
+            label: None,
+            queue: &context,
+        })
+    }
+    .unwrap();
+    let mut cmd_buffers = Vec::new();
+    for _ in 0..2 {
+        cmd_buffers.push(unsafe { raw.end_encoding() }.unwrap());
+    }
+    let write_cmd_buffers = [unsafe { raw.end_encoding() }.unwrap()];
+
+    let mut slot = None;
+    let mut submission = FailedSubmission::<Empty>::new(5);
+    submission.append(true, vec![EncoderInFlight { raw, cmd_buffers }], Vec::new());
+
+    // The queue runs out of memory, so the submission is kept.
+    let result = submission.submit(&mut slot, &write_cmd_buffers, |refs, index| {
+        assert_eq!((refs.len(), index), (3, 5));
+        Err(hal::DeviceError::OutOfMemory)
+    });
+    assert!(matches!(result, Err(QueueSubmitError::OutOfMemory(5))));
+
+    // Retrying it once memory is freed submits the same command buffers.
+    let mut submitted = None;
+    let submission = slot
+        .take()
+        .unwrap()
+        .submit(&mut slot, &write_cmd_buffers, |refs, index| {
+            submitted = Some((refs.len(), index));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(submitted, Some((3, 5)));
+    assert_eq!(submission.executions.len(), 1);
+    assert!(slot.is_none());
+}
+
+#[test]
+fn test_interleave_command_buffers() {
+    // A submission with writes and two command buffers failed, and another
+    // one with writes and a command buffer got appended to it.
+    let writes = [10, 20];
+    let executions: [&[u32]; 2] = [&[1, 2], &[3]];
+    let list = interleave_command_buffers(&writes, &[0, 1], executions.iter().cloned());
+    assert_eq!(list, [&10, &1, &2, &20, &3]);
+
+    // A submission flushing only the writes comes last.
+    let list = interleave_command_buffers(&writes, &[0, 2], executions.iter().cloned());
+    assert_eq!(list, [&10, &1, &2, &3, &20]);
+
+    // Writes of consecutive submissions without command buffers stay in order.
+    let list = interleave_command_buffers(&writes, &[1, 1], executions.iter().cloned());
+    assert_eq!(list, [&1, &2, &10, &20, &3]);
+
+    // Without any writes, the command buffers are submitted as they are.
+    let list = interleave_command_buffers(&[], &[], executions.iter().cloned());
+    assert_eq!(list, [&1, &2, &3]);
+}
+
+#[test]
+fn test_zero_write_fill() {
+    let zeros = [0u8; 64];
+    let mut data = [0u8; 64];
+    data[63] = 1;
+
+    // Disabled by default, so everything is staged.
+    assert!(!is_zero_fill(&zeros, None));
+    // Only large enough writes of zeros are filled.
+    assert!(is_zero_fill(&zeros, Some(64)));
+    assert!(is_zero_fill(&zeros, Some(4)));
+    assert!(!is_zero_fill(&zeros, Some(128)));
+    // Any non-zero byte has to be staged to end up in the buffer.
+    assert!(!is_zero_fill(&data, Some(4)));
+    // Filling gives the same contents as the staged copy would.
+    assert!(is_zero_fill(&data[..63], Some(4)));
+    assert_eq!(&data[..63], &zeros[..63]);
+}
+
+#[test]
+fn test_pixel_region_to_copy_extent() {
+    let format = wgt::TextureFormat::Bc1RgbaUnorm;
+    // Mip 2 of a 30x30 texture.
+    let mip_size = wgt::Extent3d {
+        width: 7,
+        height: 7,
+        depth_or_array_layers: 1,
+    };
+    let extent = |width, height| wgt::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    // Block-aligned regions are copied as they are.
+    let size = pixel_region_to_copy_extent(format, mip_size, wgt::Origin3d::ZERO, extent(4, 4));
+    assert_eq!(size.unwrap(), extent(4, 4));
+    // Regions reaching the edge of the mip cover its partial blocks.
+    let origin = wgt::Origin3d { x: 4, y: 0, z: 0 };
+    let size = pixel_region_to_copy_extent(format, mip_size, origin, extent(3, 7));
+    assert_eq!(size.unwrap(), extent(4, 8));
+    // Partial blocks elsewhere can't be copied.
+    match pixel_region_to_copy_extent(format, mip_size, wgt::Origin3d::ZERO, extent(2, 4)) {
+        Err(QueueWriteError::UnalignedPixelRegion {
+            what: "width",
+            value: 2,
+            block_width: 4,
+            block_height: 4,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    let origin = wgt::Origin3d { x: 0, y: 2, z: 0 };
+    match pixel_region_to_copy_extent(format, mip_size, origin, extent(4, 4)) {
+        Err(QueueWriteError::UnalignedPixelRegion {
+            what: "origin y",
+            value: 2,
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    // Uncompressed formats have no constraint.
+    let size = pixel_region_to_copy_extent(
+        wgt::TextureFormat::Rgba8Unorm,
+        mip_size,
+        wgt::Origin3d { x: 1, y: 3, z: 0 },
+        extent(5, 2),
+    );
+    assert_eq!(size.unwrap(), extent(5, 2));
+}
+
+#[test]
+fn test_copy_into_mapping() {
+    let mut memory = vec![0u8; 8];
+    let mapping = hal::BufferMapping {
+        ptr: std::ptr::NonNull::new(memory.as_mut_ptr()).unwrap(),
+        is_coherent: true,
+    };
+    unsafe {
+        copy_into_mapping(&mapping, 8, 2, &[1, 2, 3]);
+        copy_into_mapping(&mapping, 8, 5, &[4, 5, 6]);
+        mapped_slice(&mapping, 8, 0, 2).copy_from_slice(&[7, 8]);
+    }
+    assert_eq!(memory, [7, 8, 1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+#[should_panic]
+fn test_copy_into_mapping_out_of_bounds() {
+    let mut memory = vec![0u8; 8];
+    let mapping = hal::BufferMapping {
+        ptr: std::ptr::NonNull::new(memory.as_mut_ptr()).unwrap(),
+        is_coherent: true,
+    };
+    unsafe { copy_into_mapping(&mapping, 8, 6, &[1, 2, 3]) };
+}
+
+#[test]
+fn test_check_buffer_epoch() {
+    use crate::id::TypedId as _;
+
+    let stale = id::BufferId::zip(3, 1, wgt::Backend::Empty);
+    let live = id::BufferId::zip(3, 2, wgt::Backend::Empty);
+
+    // The slot was recycled after the caller got its id.
+    match check_buffer_epoch(stale, 1, Some(2)) {
+        Err(QueueWriteError::StaleBuffer {
+            expected: 1,
+            current: Some(2),
+            ..
+        }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    // The slot was freed and not reused yet.
+    assert!(matches!(
+        check_buffer_epoch(live, 2, None),
+        Err(QueueWriteError::StaleBuffer { current: None, .. })
+    ));
+    // Only the slot is compared, not the epoch carried by the id.
+    assert_eq!(check_buffer_epoch(stale, 2, Some(2)).unwrap(), live);
+    assert_eq!(check_buffer_epoch(live, 2, Some(2)).unwrap(), live);
+}
+
+#[test]
+fn test_staging_with_layout() {
+    // 64 texels of 4 bytes per row, in rows of 260 bytes.
+    let staging = || TextureStaging {
+        bytes_per_row: 260,
+        bytes_in_row: 256,
+        stage_bytes_per_row: 256,
+        height_blocks: 4,
+        block_rows_per_image: 4,
+        gpu_repack: false,
+    };
+    let layout = |bytes_per_row, rows_per_image, gpu_repack| StagingLayout {
+        bytes_per_row,
+        rows_per_image,
+        gpu_repack,
+    };
+
+    let staged = staging().with_layout(&layout(512, 4, true), 256).unwrap();
+    assert_eq!(staged.stage_bytes_per_row, 512);
+    assert!(staged.gpu_repack);
+    assert_eq!(staged.bytes_per_row, 260);
+
+    for invalid in [
+        // Not a multiple of the row pitch alignment.
+        layout(384, 4, false),
+        // Shorter than the rows.
+        layout(0, 4, false),
+        // The images are laid out as in the source.
+        layout(256, 8, false),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            staging().with_layout(invalid, 256),
+            Err(QueueWriteError::InvalidStagingLayout(_))
+        ));
+    }
+
+    // The GPU can't repack rows that aren't aligned for buffer copies.
+    let unaligned = TextureStaging {
+        bytes_per_row: 258,
+        ..staging()
+    };
+    assert!(unaligned.with_layout(&layout(256, 4, true), 256).is_err());
+    let unaligned = TextureStaging {
+        bytes_per_row: 258,
+        ..staging()
+    };
+    assert!(unaligned.with_layout(&layout(256, 4, false), 256).is_ok());
+}
@@ -22,6 +22,20 @@ pub(crate) fn new_render_bundle_encoder_descriptor<'a>(
     }
 }
 
+pub use super::queue::StagingLayout;
+
+/// Version of the trace format, recorded in `Action::Init`.
+///
+/// Version 2 added the `staging` layout of `WriteTexture`, which replaying
+/// reproduces.
+pub const FILE_VERSION: u32 = 2;
+
+/// Traces recorded before the format got versioned.
+#[cfg(feature = "replay")]
+fn unversioned() -> u32 {
+    1
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 #[cfg_attr(feature = "trace", derive(serde::Serialize))]
@@ -30,6 +44,8 @@ pub enum Action<'a> {
     Init {
         desc: crate::device::DeviceDescriptor<'a>,
         backend: wgt::Backend,
+        #[cfg_attr(feature = "replay", serde(default = "unversioned"))]
+        version: u32,
     },
     CreateBuffer(id::BufferId, crate::resource::BufferDescriptor<'a>),
     FreeBuffer(id::BufferId),
@@ -108,7 +124,16 @@ pub enum Action<'a> {
         data: FileName,
         layout: wgt::ImageDataLayout,
         size: wgt::Extent3d,
+        /// Missing from version 1 traces, and if the texture is invalid.
+        #[cfg_attr(any(feature = "replay", feature = "trace"), serde(default))]
+        staging: Option<StagingLayout>,
     },
+    /// Threshold of the zero writes that are filled instead of staged,
+    /// see `device_set_zero_write_fill_threshold`.
+    SetZeroWriteFillThreshold(Option<wgt::BufferAddress>),
+    /// Label of the submission with the given index,
+    /// recorded before its `Submit` actions.
+    LabelSubmission(crate::SubmissionIndex, String),
     Submit(crate::SubmissionIndex, Vec<Command>),
 }
 
@@ -133,6 +158,13 @@ pub enum Command {
         dst: crate::command::ImageCopyBuffer,
         size: wgt::Extent3d,
     },
+    /// Copy done by `command_encoder_copy_texture_to_buffer_tight`, with
+    /// the buffer layout it resolved for the recording device.
+    CopyTextureToBufferTight {
+        src: crate::command::ImageCopyTexture,
+        dst: crate::command::ImageCopyBuffer,
+        size: wgt::Extent3d,
+    },
     CopyTextureToTexture {
         src: crate::command::ImageCopyTexture,
         dst: crate::command::ImageCopyTexture,
@@ -143,6 +175,12 @@ pub enum Command {
         offset: wgt::BufferAddress,
         size: Option<wgt::BufferSize>,
     },
+    FillBuffer {
+        dst: id::BufferId,
+        offset: wgt::BufferAddress,
+        size: Option<wgt::BufferSize>,
+        value: u32,
+    },
     ClearImage {
         dst: id::TextureId,
         subresource_range: wgt::ImageSubresourceRange,
@@ -1,7 +1,7 @@
 use crate::{
     binding_model::{BindGroup, BindGroupLayout, PipelineLayout},
     command::{CommandBuffer, RenderBundle},
-    device::Device,
+    device::{Device, ExternalFenceHandle},
     id,
     instance::{Adapter, Instance, Surface},
     pipeline::{ComputePipeline, RenderPipeline, ShaderModule},
@@ -121,6 +121,16 @@ impl<T, I: id::TypedId> Storage<T, I> {
         }
     }
 
+    /// Get the epoch of whatever currently occupies the slot of this ID,
+    /// or `None` if the slot is empty.
+    pub(crate) fn current_epoch(&self, id: I) -> Option<Epoch> {
+        let (index, _, _) = id.unzip();
+        match self.map.get(index as usize) {
+            None | Some(&Element::Vacant) => None,
+            Some(&Element::Occupied(_, epoch)) | Some(&Element::Error(epoch, ..)) => Some(epoch),
+        }
+    }
+
     /// Get a reference to an item behind a potentially invalid ID.
     /// Panics if there is an epoch mismatch, or the entry is empty.
     pub(crate) fn get(&self, id: I) -> Result<&T, InvalidId> {
@@ -935,6 +945,7 @@ pub trait HalApi: hal::Api {
     const VARIANT: Backend;
     fn hub<G: GlobalIdentityHandlerFactory>(global: &Global<G>) -> &Hub<Self, G>;
     fn get_surface_mut(surface: &mut Surface) -> &mut Self::Surface;
+    fn fence_handle(fence: &Self::Fence) -> Option<ExternalFenceHandle>;
 }
 
 #[cfg(vulkan)]
@@ -946,6 +957,11 @@ impl HalApi for hal::api::Vulkan {
     fn get_surface_mut(surface: &mut Surface) -> &mut Self::Surface {
         surface.vulkan.as_mut().unwrap()
     }
+    fn fence_handle(fence: &Self::Fence) -> Option<ExternalFenceHandle> {
+        fence
+            .raw_timeline_semaphore()
+            .map(ExternalFenceHandle::VulkanTimelineSemaphore)
+    }
 }
 
 #[cfg(metal)]
@@ -957,6 +973,9 @@ impl HalApi for hal::api::Metal {
     fn get_surface_mut(surface: &mut Surface) -> &mut Self::Surface {
         surface.metal.as_mut().unwrap()
     }
+    fn fence_handle(_fence: &Self::Fence) -> Option<ExternalFenceHandle> {
+        None
+    }
 }
 
 #[cfg(dx12)]
@@ -968,6 +987,9 @@ impl HalApi for hal::api::Dx12 {
     fn get_surface_mut(surface: &mut Surface) -> &mut Self::Surface {
         surface.dx12.as_mut().unwrap()
     }
+    fn fence_handle(fence: &Self::Fence) -> Option<ExternalFenceHandle> {
+        Some(ExternalFenceHandle::Dx12Fence(fence.raw_fence() as *mut _))
+    }
 }
 
 /*
@@ -992,6 +1014,9 @@ impl HalApi for hal::api::Gles {
     fn get_surface_mut(surface: &mut Surface) -> &mut Self::Surface {
         surface.gl.as_mut().unwrap()
     }
+    fn fence_handle(_fence: &Self::Fence) -> Option<ExternalFenceHandle> {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -170,6 +170,24 @@ impl MemoryInitTracker {
     pub(crate) fn clear(&mut self, range: Range<wgt::BufferAddress>) {
         self.drain(range).for_each(drop);
     }
+
+    // Checks if a write to `range` initializes any memory, like `check`.
+    // A write covering all of the `size` bytes returns the whole range, so that
+    // it can be cleared in one go.
+    pub(crate) fn check_write(
+        &self,
+        range: Range<wgt::BufferAddress>,
+        size: wgt::BufferAddress,
+    ) -> Option<Range<wgt::BufferAddress>> {
+        let overwrites_all = range.start == 0 && range.end >= size;
+        self.check(range)
+            .map(|range| if overwrites_all { 0..size } else { range })
+    }
+
+    // Clears all uninitialized ranges at once, without looking them up.
+    pub(crate) fn clear_all(&mut self) {
+        self.uninitialized_ranges.clear();
+    }
 }
 
 #[cfg(test)]
@@ -231,6 +249,31 @@ mod test {
         assert_eq!(tracker.check(0..30), None);
     }
 
+    #[test]
+    fn clear_all_fragmented() {
+        let mut tracker = MemoryInitTracker::new(30);
+        tracker.clear(2..4);
+        tracker.clear(10..20);
+        tracker.clear(26..27);
+
+        tracker.clear_all();
+
+        assert_eq!(tracker.check(0..30), None);
+        assert_eq!(tracker.drain(0..30).count(), 0);
+    }
+
+    #[test]
+    fn check_write_covering_everything() {
+        let mut tracker = MemoryInitTracker::new(30);
+        tracker.clear(0..4);
+        tracker.clear(10..20);
+
+        assert_eq!(tracker.check_write(0..30, 30), Some(0..30));
+        assert_eq!(tracker.check_write(0..8, 30), Some(4..8));
+        tracker.clear(0..30);
+        assert_eq!(tracker.check_write(0..30, 30), None);
+    }
+
     #[test]
     fn drain_never_returns_ranges_twice_for_same_range() {
         let mut tracker = MemoryInitTracker::new(19);
@@ -114,6 +114,25 @@ pub(crate) struct BufferPendingMapping {
 
 pub type BufferDescriptor<'a> = wgt::BufferDescriptor<Label<'a>>;
 
+/// How the ranges of a buffer that were never written to are initialized
+/// before being used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferInitPolicy {
+    /// Ranges that are read before being written to are filled with zeros
+    /// on submission.
+    Zero,
+    /// The buffer is never initialized. The user asserts that every range is
+    /// written before it is read, reading a range that wasn't written to is
+    /// undefined behavior.
+    Skip,
+}
+
+impl Default for BufferInitPolicy {
+    fn default() -> Self {
+        Self::Zero
+    }
+}
+
 #[derive(Debug)]
 pub struct Buffer<A: hal::Api> {
     pub(crate) raw: Option<A::Buffer>,
@@ -121,6 +140,7 @@ pub struct Buffer<A: hal::Api> {
     pub(crate) usage: wgt::BufferUsages,
     pub(crate) size: wgt::BufferAddress,
     pub(crate) initialization_status: MemoryInitTracker,
+    pub(crate) init_policy: BufferInitPolicy,
     pub(crate) sync_mapped_writes: Option<hal::MemoryRange>,
     pub(crate) life_guard: LifeGuard,
     pub(crate) map_state: BufferMapState<A>,
@@ -156,6 +176,8 @@ impl<A: hal::Api> Borrow<()> for Buffer<A> {
 
 pub type TextureDescriptor<'a> = wgt::TextureDescriptor<Label<'a>>;
 
+pub use hal::TextureTiling;
+
 #[derive(Debug)]
 pub struct Texture<A: hal::Api> {
     pub(crate) raw: Option<A::Texture>,
@@ -55,6 +55,11 @@ pub struct SwapChain<A: hal::Api> {
     pub(crate) num_frames: u32,
     pub(crate) acquired_texture: Option<(Stored<TextureViewId>, A::SurfaceTexture)>,
     pub(crate) active_submission_index: SubmissionIndex,
+    /// Number of textures acquired from the swap chain, which numbers the
+    /// frame of the texture that is currently acquired.
+    pub(crate) acquired_frames: u64,
+    /// Number of acquired textures that got presented.
+    pub(crate) presented_frames: u64,
     pub(crate) marker: PhantomData<A>,
 }
 
@@ -226,6 +231,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                     },
                     suf_texture,
                 ));
+                sc.acquired_frames += 1;
 
                 Some(id.0)
             }
@@ -265,6 +271,7 @@ impl<G: GlobalIdentityHandlerFactory> Global<G> {
                 .acquired_texture
                 .take()
                 .ok_or(SwapChainError::AlreadyAcquired)?;
+            sc.presented_frames += 1;
 
             drop(swap_chain_guard);
 
@@ -216,7 +216,12 @@ impl crate::CommandEncoder<super::Api> for super::CommandEncoder {
         }
     }
 
-    unsafe fn fill_buffer(&mut self, buffer: &super::Buffer, range: crate::MemoryRange, value: u8) {
+    unsafe fn fill_buffer(
+        &mut self,
+        buffer: &super::Buffer,
+        range: crate::MemoryRange,
+        value: u32,
+    ) {
         assert_eq!(value, 0, "Only zero is supported!");
         let list = self.list.unwrap();
         let mut offset = range.start;
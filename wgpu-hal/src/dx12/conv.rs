@@ -191,6 +191,16 @@ pub fn map_buffer_usage_to_resource_flags(usage: crate::BufferUses) -> d3d12::D3
     flags
 }
 
+pub fn map_texture_layout(layout: d3d12::D3D12_TEXTURE_LAYOUT) -> crate::TextureTiling {
+    match layout {
+        d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR => crate::TextureTiling::Linear,
+        // Swizzled by the driver, in a pattern picked for the GPU.
+        d3d12::D3D12_TEXTURE_LAYOUT_UNKNOWN
+        | d3d12::D3D12_TEXTURE_LAYOUT_64KB_UNDEFINED_SWIZZLE => crate::TextureTiling::Optimal,
+        _ => crate::TextureTiling::Unknown,
+    }
+}
+
 pub fn map_texture_dimension(dim: wgt::TextureDimension) -> d3d12::D3D12_RESOURCE_DIMENSION {
     match dim {
         wgt::TextureDimension::D1 => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE1D,
@@ -772,11 +772,15 @@ impl crate::Device<super::Api> for super::Device {
             size: desc.size,
             mip_level_count: desc.mip_level_count,
             sample_count: desc.sample_count,
+            layout: raw_desc.Layout,
         })
     }
     unsafe fn destroy_texture(&self, texture: super::Texture) {
         texture.resource.destroy();
     }
+    unsafe fn texture_tiling(&self, texture: &super::Texture) -> crate::TextureTiling {
+        conv::map_texture_layout(texture.layout)
+    }
 
     unsafe fn create_texture_view(
         &self,
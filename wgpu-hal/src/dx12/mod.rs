@@ -335,6 +335,7 @@ pub struct Texture {
     size: wgt::Extent3d,
     mip_level_count: u32,
     sample_count: u32,
+    layout: d3d12::D3D12_TEXTURE_LAYOUT,
 }
 
 unsafe impl Send for Texture {}
@@ -355,7 +356,13 @@ impl Texture {
     }
 
     fn calc_subresource_for_copy(&self, base: &crate::TextureCopyBase) -> u32 {
-        self.calc_subresource(base.mip_level, base.array_layer, 0)
+        // stencil lives in its own plane in depth/stencil formats
+        let plane = if base.aspect == crate::FormatAspects::STENCIL {
+            1
+        } else {
+            0
+        };
+        self.calc_subresource(base.mip_level, base.array_layer, plane)
     }
 }
 
@@ -398,6 +405,13 @@ pub struct Fence {
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+impl Fence {
+    /// Returns the raw `ID3D12Fence`, without adding a reference to it.
+    pub fn raw_fence(&self) -> *mut d3d12::ID3D12Fence {
+        self.raw.as_mut_ptr()
+    }
+}
+
 pub struct BindGroupLayout {
     /// Sorted list of entries.
     entries: Vec<wgt::BindGroupLayoutEntry>,
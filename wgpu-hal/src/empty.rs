@@ -128,6 +128,9 @@ impl crate::Device<Api> for Context {
         Ok(Resource)
     }
     unsafe fn destroy_texture(&self, texture: Resource) {}
+    unsafe fn texture_tiling(&self, texture: &Resource) -> crate::TextureTiling {
+        crate::TextureTiling::Unknown
+    }
     unsafe fn create_texture_view(
         &self,
         texture: &Resource,
@@ -245,7 +248,7 @@ impl crate::CommandEncoder<Api> for Encoder {
     {
     }
 
-    unsafe fn fill_buffer(&mut self, buffer: &Resource, range: crate::MemoryRange, value: u8) {}
+    unsafe fn fill_buffer(&mut self, buffer: &Resource, range: crate::MemoryRange, value: u32) {}
 
     unsafe fn copy_buffer_to_buffer<T>(&mut self, src: &Resource, dst: &Resource, regions: T) {}
 
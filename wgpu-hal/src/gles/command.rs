@@ -234,7 +234,12 @@ impl crate::CommandEncoder<super::Api> for super::CommandEncoder {
         }
     }
 
-    unsafe fn fill_buffer(&mut self, buffer: &super::Buffer, range: crate::MemoryRange, value: u8) {
+    unsafe fn fill_buffer(
+        &mut self,
+        buffer: &super::Buffer,
+        range: crate::MemoryRange,
+        value: u32,
+    ) {
         self.cmd_buffer.commands.push(C::FillBuffer {
             dst: buffer.raw,
             dst_target: buffer.target,
@@ -564,6 +564,10 @@ impl crate::Device<super::Api> for super::Device {
             }
         }
     }
+    unsafe fn texture_tiling(&self, _texture: &super::Texture) -> crate::TextureTiling {
+        // GL keeps the layout of its textures private.
+        crate::TextureTiling::Unknown
+    }
 
     unsafe fn create_texture_view(
         &self,
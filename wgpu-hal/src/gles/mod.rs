@@ -167,7 +167,7 @@ pub struct Queue {
     copy_fbo: glow::Framebuffer,
     /// Keep a reasonably large buffer filled with zeroes,
     /// so that we can implement `FillBuffer` of zeroes
-    /// by copying from it.
+    /// by copying from it. Other patterns are uploaded.
     zero_buffer: glow::Buffer,
     temp_query_results: Vec<u64>,
 }
@@ -507,7 +507,7 @@ enum Command {
         dst: glow::Buffer,
         dst_target: BindTarget,
         range: crate::MemoryRange,
-        value: u8,
+        value: u32,
     },
     CopyBufferToBuffer {
         src: glow::Buffer,
@@ -159,20 +159,41 @@ impl super::Queue {
                 ref range,
                 value,
             } => {
-                assert_eq!(value, 0); // other values require `wgt::Features::CLEAR_COMMANDS`.
-                gl.bind_buffer(glow::COPY_READ_BUFFER, Some(self.zero_buffer));
                 gl.bind_buffer(dst_target, Some(dst));
                 let mut dst_offset = range.start;
-                while dst_offset < range.end {
-                    let size = (range.end - dst_offset).min(super::ZERO_BUFFER_SIZE as u64);
-                    gl.copy_buffer_sub_data(
-                        glow::COPY_READ_BUFFER,
-                        dst_target,
-                        0,
-                        dst_offset as i32,
-                        size as i32,
-                    );
-                    dst_offset += size;
+                if value == 0 {
+                    gl.bind_buffer(glow::COPY_READ_BUFFER, Some(self.zero_buffer));
+                    while dst_offset < range.end {
+                        let size = (range.end - dst_offset).min(super::ZERO_BUFFER_SIZE as u64);
+                        gl.copy_buffer_sub_data(
+                            glow::COPY_READ_BUFFER,
+                            dst_target,
+                            0,
+                            dst_offset as i32,
+                            size as i32,
+                        );
+                        dst_offset += size;
+                    }
+                } else {
+                    // Upload the pattern, at most a zero buffer worth of it at once.
+                    let chunk_size =
+                        (range.end - range.start).min(super::ZERO_BUFFER_SIZE as u64) as usize;
+                    let chunk = value
+                        .to_ne_bytes()
+                        .iter()
+                        .cloned()
+                        .cycle()
+                        .take(chunk_size)
+                        .collect::<Vec<u8>>();
+                    while dst_offset < range.end {
+                        let size = (range.end - dst_offset).min(chunk_size as u64);
+                        gl.buffer_sub_data_u8_slice(
+                            dst_target,
+                            dst_offset as i32,
+                            &chunk[..size as usize],
+                        );
+                        dst_offset += size;
+                    }
                 }
             }
             C::CopyBufferToBuffer {
@@ -229,6 +229,8 @@ pub trait Device<A: Api>: Send + Sync {
     /// The initial usage for all subresources is `TextureUses::UNINITIALIZED`.
     unsafe fn create_texture(&self, desc: &TextureDescriptor) -> Result<A::Texture, DeviceError>;
     unsafe fn destroy_texture(&self, texture: A::Texture);
+    /// Returns the memory layout of the texels of `texture`.
+    unsafe fn texture_tiling(&self, texture: &A::Texture) -> TextureTiling;
     unsafe fn create_texture_view(
         &self,
         texture: &A::Texture,
@@ -343,9 +345,11 @@ pub trait CommandEncoder<A: Api>: Send + Sync {
 
     // copy operations
 
-    /// This is valid to call with `value == 0`.
-    /// Otherwise `wgt::Features::CLEAR_COMMANDS` is required.
-    unsafe fn fill_buffer(&mut self, buffer: &A::Buffer, range: MemoryRange, value: u8);
+    /// Fill the range with the 4-byte `value` pattern, repeated.
+    ///
+    /// Vulkan and GL support any value. Metal only supports patterns of
+    /// a single repeated byte, and DX12 only supports `value == 0`.
+    unsafe fn fill_buffer(&mut self, buffer: &A::Buffer, range: MemoryRange, value: u32);
 
     unsafe fn copy_buffer_to_buffer<T>(&mut self, src: &A::Buffer, dst: &A::Buffer, regions: T)
     where
@@ -743,6 +747,17 @@ pub struct TextureDescriptor<'a> {
     pub memory_flags: MemoryFlags,
 }
 
+/// Memory layout of the texels of a texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureTiling {
+    /// Texels are stored row by row.
+    Linear,
+    /// Texels are stored in the layout preferred by the GPU for sampling.
+    Optimal,
+    /// The backend doesn't expose the layout.
+    Unknown,
+}
+
 /// TextureView descriptor.
 ///
 /// Valid usage:
@@ -120,9 +120,19 @@ impl crate::CommandEncoder<super::Api> for super::CommandEncoder {
     {
     }
 
-    unsafe fn fill_buffer(&mut self, buffer: &super::Buffer, range: crate::MemoryRange, value: u8) {
+    unsafe fn fill_buffer(
+        &mut self,
+        buffer: &super::Buffer,
+        range: crate::MemoryRange,
+        value: u32,
+    ) {
+        let bytes = value.to_le_bytes();
+        assert!(
+            bytes.iter().all(|&byte| byte == bytes[0]),
+            "Only patterns of a repeated byte are supported!"
+        );
         let encoder = self.enter_blit();
-        encoder.fill_buffer(&buffer.raw, conv::map_range(&range), value);
+        encoder.fill_buffer(&buffer.raw, conv::map_range(&range), bytes[0]);
     }
 
     unsafe fn copy_buffer_to_buffer<T>(
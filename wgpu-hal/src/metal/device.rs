@@ -271,6 +271,11 @@ impl crate::Device<super::Api> for super::Device {
 
     unsafe fn destroy_texture(&self, _texture: super::Texture) {}
 
+    unsafe fn texture_tiling(&self, _texture: &super::Texture) -> crate::TextureTiling {
+        // Metal keeps the layout of its textures private.
+        crate::TextureTiling::Unknown
+    }
+
     unsafe fn create_texture_view(
         &self,
         texture: &super::Texture,
@@ -663,6 +663,7 @@ impl crate::Device<super::Api> for super::Device {
         if desc.dimension == wgt::TextureDimension::D2 && desc.size.depth_or_array_layers % 6 == 0 {
             raw_flags |= vk::ImageCreateFlags::CUBE_COMPATIBLE;
         }
+        let raw_tiling = vk::ImageTiling::OPTIMAL;
 
         let vk_info = vk::ImageCreateInfo::builder()
             .flags(raw_flags)
@@ -676,7 +677,7 @@ impl crate::Device<super::Api> for super::Device {
             .mip_levels(desc.mip_level_count)
             .array_layers(array_layer_count)
             .samples(vk::SampleCountFlags::from_raw(desc.sample_count))
-            .tiling(vk::ImageTiling::OPTIMAL)
+            .tiling(raw_tiling)
             .usage(conv::map_texture_usage(desc.usage))
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .initial_layout(vk::ImageLayout::UNDEFINED);
@@ -710,6 +711,7 @@ impl crate::Device<super::Api> for super::Device {
             aspects: crate::FormatAspects::from(desc.format),
             format_info: desc.format.describe(),
             raw_flags,
+            raw_tiling: Some(raw_tiling),
         })
     }
     unsafe fn destroy_texture(&self, texture: super::Texture) {
@@ -718,6 +720,13 @@ impl crate::Device<super::Api> for super::Device {
             .lock()
             .dealloc(&*self.shared, texture.block.unwrap());
     }
+    unsafe fn texture_tiling(&self, texture: &super::Texture) -> crate::TextureTiling {
+        match texture.raw_tiling {
+            Some(vk::ImageTiling::LINEAR) => crate::TextureTiling::Linear,
+            Some(vk::ImageTiling::OPTIMAL) => crate::TextureTiling::Optimal,
+            _ => crate::TextureTiling::Unknown,
+        }
+    }
 
     unsafe fn create_texture_view(
         &self,
@@ -650,6 +650,7 @@ impl crate::Surface<super::Api> for super::Surface {
                 aspects: crate::FormatAspects::COLOR,
                 format_info: sc.config.format.describe(),
                 raw_flags: vk::ImageCreateFlags::empty(),
+                raw_tiling: None,
             },
         };
         Ok(Some(crate::AcquiredSurfaceTexture {
@@ -254,6 +254,8 @@ pub struct Texture {
     aspects: crate::FormatAspects,
     format_info: wgt::TextureFormatInfo,
     raw_flags: vk::ImageCreateFlags,
+    /// Tiling the image was created with, unknown for swap chain images.
+    raw_tiling: Option<vk::ImageTiling>,
 }
 
 #[derive(Debug)]
@@ -399,6 +401,16 @@ impl Fence {
         }
     }
 
+    /// Returns the raw handle of the timeline semaphore backing this fence,
+    /// or `None` if the device doesn't support timeline semaphores.
+    pub fn raw_timeline_semaphore(&self) -> Option<u64> {
+        use ash::vk::Handle as _;
+        match *self {
+            Self::TimelineSemaphore(raw) => Some(raw.as_raw()),
+            Self::FencePool { .. } => None,
+        }
+    }
+
     fn maintain(&mut self, device: &ash::Device) -> Result<(), crate::DeviceError> {
         match *self {
             Self::TimelineSemaphore(_) => {}